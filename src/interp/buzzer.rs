@@ -0,0 +1,30 @@
+// the tone that plays while the sound timer is non-zero
+pub trait Buzzer: BuzzerClone {
+    // called each time the sound timer leaves zero (true) or gets back to it (false)
+    fn set_playing(&mut self, playing: bool);
+}
+
+// lets VM keep deriving Clone while it holds a boxed buzzer
+pub trait BuzzerClone {
+    fn clone_box(&self) -> Box<dyn Buzzer>;
+}
+
+impl<T: 'static + Buzzer + Clone> BuzzerClone for T {
+    fn clone_box(&self) -> Box<dyn Buzzer> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Buzzer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// the buzzer a VM starts with, makes no sound
+#[derive(Clone, Default)]
+pub struct SilentBuzzer;
+
+impl Buzzer for SilentBuzzer {
+    fn set_playing(&mut self, _playing: bool) {}
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+// the delay and sound timers always count down at 60Hz, whatever the instruction rate
+pub const TIMER_HZ: u32 = 60;
+
+// paces a VM for a frontend: once per 60Hz frame run instructions_for_next_frame() ticks, then one tick_timers(),
+// VM::step_frame does exactly that
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clock {
+    instructions_per_second: u32,
+    // instructions carried over from earlier frames, in 1/60 of an instruction
+    carry: u32,
+}
+
+impl Clock {
+    pub fn new(instructions_per_second: u32) -> Clock {
+        Clock {
+            instructions_per_second,
+            carry: 0,
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    // how long a frame lasts on the wall clock
+    pub fn frame_duration(&self) -> Duration {
+        Duration::from_secs(1) / TIMER_HZ
+    }
+
+    // a rate that is not a multiple of 60 is spread over the frames,
+    // e.g. 700 per second gives frames of 11 and 12 instructions adding up to exactly 700 every 60 frames
+    pub fn instructions_for_next_frame(&mut self) -> usize {
+        let owed = self.carry + self.instructions_per_second;
+        self.carry = owed % TIMER_HZ;
+        (owed / TIMER_HZ) as usize
+    }
+}
+
+// 700 instructions per second suits most CHIP-8 games
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock::new(700)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_add_up_to_the_instruction_rate() {
+        let mut clock = Clock::default();
+        let frames: Vec<usize> = (0..TIMER_HZ)
+            .map(|_| clock.instructions_for_next_frame())
+            .collect();
+        assert_eq!(frames.iter().sum::<usize>(), 700);
+        assert!(frames.iter().all(|&n| n == 11 || n == 12));
+        assert_eq!(Clock::new(600).instructions_for_next_frame(), 10);
+    }
+}
@@ -0,0 +1,133 @@
+use super::error::DecodeError;
+use super::opcode::OpCode;
+
+// walks a ROM two bytes at a time, can be paused and resumed between items
+pub struct Disassembler<'a> {
+    bytes: &'a [u8],
+    start: u16,
+    offset: usize,
+}
+
+impl<'a> Disassembler<'a> {
+    // start is the address the first byte is loaded at, usually 0x200
+    pub fn new(bytes: &'a [u8], start: u16) -> Disassembler<'a> {
+        Disassembler {
+            bytes,
+            start,
+            offset: 0,
+        }
+    }
+
+    // address of the next instruction to be yielded
+    pub fn position(&self) -> u16 {
+        self.start.wrapping_add(self.offset as u16)
+    }
+}
+
+impl<'a> Iterator for Disassembler<'a> {
+    // address, the raw word and what it decodes to
+    type Item = (u16, u16, Result<OpCode, DecodeError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // a dangling odd byte at the end is not an instruction,
+        // and neither is anything past the top of the 16-bit address space
+        if self.offset + 1 >= self.bytes.len() || self.start as usize + self.offset > 0xFFFF {
+            return None;
+        }
+        let addr = Disassembler::position(self);
+        let word = u16::from_be_bytes([self.bytes[self.offset], self.bytes[self.offset + 1]]);
+        self.offset += 2;
+        Some((addr, word, OpCode::try_from(word)))
+    }
+}
+
+// a full listing of a program, words that do not decode are kept as data along with why:
+// "DB 0x5121 ; 0x5121 has its low nibble set where it must be zero"
+pub fn disassemble(bytes: &[u8], base: u16) -> Vec<(u16, String)> {
+    Disassembler::new(bytes, base)
+        .map(|(addr, word, op)| match op {
+            Ok(op) => (addr, op.into()),
+            Err(e) => (addr, format!("DB 0x{:04X} ; {}", word, e)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interp::opcode::{Addr, Byte, V};
+
+    #[test]
+    fn disassembler_can_pause_and_resume() {
+        // CLS ; LD V1, 0x02 ; 0x5121 ; JP 0x200 ; a dangling odd byte
+        let rom = [0x00, 0xE0, 0x61, 0x02, 0x51, 0x21, 0x12, 0x00, 0xFF];
+        let mut dis = Disassembler::new(&rom, 0x200);
+        let first: Vec<_> = dis.by_ref().take(2).collect();
+        assert_eq!(
+            first,
+            vec![
+                (0x200, 0x00E0, Ok(OpCode::ClearScreen)),
+                (0x202, 0x6102, Ok(OpCode::Load(V(1), Byte(0x02)))),
+            ]
+        );
+        assert_eq!(dis.position(), 0x204);
+        assert_eq!(
+            dis.next(),
+            Some((0x204, 0x5121, Err(DecodeError::ReservedBitsSet(0x5121))))
+        );
+        assert_eq!(
+            dis.next(),
+            Some((0x206, 0x1200, Ok(OpCode::Jump(Addr(0x200)))))
+        );
+        assert_eq!(dis.next(), None);
+        assert_eq!(dis.position(), 0x208);
+    }
+
+    #[test]
+    fn disassembler_stops_at_the_top_of_the_address_space() {
+        assert_eq!(
+            disassemble(&[0; 4], 0xFFFE),
+            vec![(0xFFFE, "SYS 0x000".to_owned())]
+        );
+    }
+
+    #[test]
+    fn data_words_say_why_they_did_not_decode() {
+        // CLS ; 0x5011 ; 0xF099
+        let listing = disassemble(&[0x00, 0xE0, 0x50, 0x11, 0xF0, 0x99], 0x200);
+        assert_eq!(
+            listing,
+            vec![
+                (0x200, "CLS".to_owned()),
+                (
+                    0x202,
+                    "DB 0x5011 ; 0x5011 has its low nibble set where it must be zero".to_owned()
+                ),
+                (
+                    0x204,
+                    "DB 0xF099 ; Fx99 is not a known Fx opcode".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn disassemble_lists_a_small_program() {
+        // LD I, 0x20A ; LD V0, 0x0C ; DRW V0, V0, 5 ; JP 0x206 ; CLS
+        let rom = [0xA2, 0x0A, 0x60, 0x0C, 0xD0, 0x05, 0x12, 0x06, 0x00, 0xE0];
+        let listing: Vec<String> = disassemble(&rom, 0x200)
+            .into_iter()
+            .map(|(addr, text)| format!("{:03X}: {}", addr, text))
+            .collect();
+        assert_eq!(
+            listing,
+            vec![
+                "200: LD I, 0x20A",
+                "202: LD V0, 0x0C",
+                "204: DRW V0, V0, 5",
+                "206: JP 0x206",
+                "208: CLS",
+            ]
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use thiserror::Error;
+
+// recoverable failures while executing a program, so a bad ROM cannot abort the host
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VmError {
+    #[error("rom of {0} bytes does not fit in program memory")]
+    RomTooLarge(usize),
+    #[error("program start {0:#05x} is outside program memory 0x200 - 0xfff")]
+    InvalidProgramStart(u16),
+    #[error("a font at {0:#05x} does not fit in the interpreter area below 0x200")]
+    InvalidFontBase(u16),
+    #[error("memory access at {0:#05x} is out of bounds")]
+    MemoryAccess(usize),
+    #[error("call nested deeper than the 16 stack levels")]
+    StackOverflow,
+    #[error("return with an empty stack")]
+    StackUnderflow,
+    #[error("sprite at {0:#05x} is in the interpreter area below 0x200")]
+    DrawFromReserved(u16),
+    #[error("write to {0:#05x} in the interpreter area below 0x200")]
+    WriteToReserved(usize),
+    #[error("save state was made with a rom of checksum {0:#010x}, the loaded rom has {1:#010x}")]
+    SaveStateRomMismatch(u32, u32),
+}
+
+// why a 16-bit word is not a valid instruction, or why there is no word to decode
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum DecodeError {
+    #[error("{0:#06x} is not a known opcode")]
+    UnknownOpcode(u16),
+    #[error("{0:#06x} has its low nibble set where it must be zero")]
+    ReservedBitsSet(u16),
+    #[error("Fx{0:02X} is not a known Fx opcode")]
+    UnknownFx(u8),
+    #[error("no instruction at {0:#05x}, it runs past the end of memory")]
+    AddressOutOfRange(u16),
+}
+
+// why a line of text could not be assembled into an instruction
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AssembleError {
+    #[error("\"{0}\" is not a known instruction")]
+    UnknownInstruction(String),
+    #[error("{0} is not a valid operand")]
+    InvalidOperand(String),
+    #[error("{0} does not fit in its operand field")]
+    OperandOutOfRange(String),
+    #[error("{0} bytes of code at {1:#05x} run past the end of memory")]
+    DoesNotFit(usize, u16),
+}
@@ -0,0 +1,62 @@
+/* Computers which originally used the Chip-8 Language had a 16-key hexadecimal keypad with the following layout:
++-+-+-+-+
+|1|2|3|C|
++-+-+-+-+
+|4|5|6|D|
++-+-+-+-+
+|7|8|9|E|
++-+-+-+-+
+|A|0|B|F|
++-+-+-+-+
+*/
+// what Ex9E, ExA1 and Fx0A read, key is always 0x0 - 0xF
+pub trait Keypad: KeypadClone {
+    fn is_down(&self, key: u8) -> bool;
+
+    // asked once per tick while Fx0A waits, None keeps it waiting
+    fn wait_key(&mut self) -> Option<u8>;
+
+    // host key events from VM::set_key, a keypad driven some other way can ignore them
+    fn set_key(&mut self, _key: u8, _pressed: bool) {}
+}
+
+// lets VM keep deriving Clone while it holds a boxed keypad
+pub trait KeypadClone {
+    fn clone_box(&self) -> Box<dyn Keypad>;
+}
+
+impl<T: 'static + Keypad + Clone> KeypadClone for T {
+    fn clone_box(&self) -> Box<dyn Keypad> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Keypad> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// the keypad a VM starts with, keys are held and released through VM::set_key
+#[derive(Clone, Default)]
+pub struct MemoryKeypad {
+    keys: [bool; 16], // true means the key is held down
+}
+
+impl Keypad for MemoryKeypad {
+    fn is_down(&self, key: u8) -> bool {
+        self.keys.get(key as usize).copied().unwrap_or(false)
+    }
+
+    // the lowest held key wins when several are down
+    fn wait_key(&mut self) -> Option<u8> {
+        self.keys.iter().position(|&down| down).map(|key| key as u8)
+    }
+
+    // keys above 0xF do not exist and are ignored
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        if let Some(k) = self.keys.get_mut(key as usize) {
+            *k = pressed;
+        }
+    }
+}
@@ -1,5 +1,12 @@
 // the specification is from http://devernay.free.fr/hacks/chip8/C8TECH10.HTM#00E0
 
+pub mod buzzer;
+pub mod clock;
+pub mod disasm;
+pub mod error;
+pub mod keypad;
 pub mod opcode;
+pub mod rng;
+pub mod screen;
 mod sprites;
 pub mod vm;
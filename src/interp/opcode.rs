@@ -1,9 +1,36 @@
+use std::fmt;
+
+use super::error::{AssembleError, DecodeError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Byte(pub u8); // kk
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Addr(pub u16); // nnn
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct V(pub u8); // 0x00 - 0x0f
 
+// operands print the way common CHIP-8 tooling writes them: VA, 0x23, 0x200
+impl fmt::Display for Byte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:02X}", self.0)
+    }
+}
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:03X}", self.0)
+    }
+}
+
+impl fmt::Display for V {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "V{:X}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum OpCode {
     /* 0nnn - SYS addr
     Jump to a machine code routine at nnn.
@@ -214,22 +241,306 @@ pub enum OpCode {
     The interpreter reads values from memory starting at location I into registers V0 through Vx.
     */
     LoadRegisters(V),
+
+    /* 00Cn - SCD nibble (SUPER-CHIP)
+    Scroll the display down by n pixels.
+    The rows scrolled in at the top are blank.
+    */
+    ScrollDown(u8),
+
+    /* 00FB - SCR (SUPER-CHIP)
+    Scroll the display right by 4 pixels.
+    The columns scrolled in at the left are blank.
+    */
+    ScrollRight,
+
+    /* 00FC - SCL (SUPER-CHIP)
+    Scroll the display left by 4 pixels.
+    The columns scrolled in at the right are blank.
+    */
+    ScrollLeft,
+
+    /* 00FE - LOW (SUPER-CHIP)
+    Switch to the 64x32 low resolution display.
+    The display is cleared.
+    */
+    LowRes,
+
+    /* 00FF - HIGH (SUPER-CHIP)
+    Switch to the 128x64 high resolution display.
+    The display is cleared.
+    */
+    HighRes,
+
+    /* Fx30 - LD HF, Vx (SUPER-CHIP)
+    Set I = location of the large sprite for digit Vx.
+    The value of I is set to the location of the 8x10 sprite for the decimal digit in Vx.
+    */
+    LoadLargeSprite(V),
+
+    /* 00Dn - SCU nibble (XO-CHIP)
+    Scroll the display up by n pixels.
+    The rows scrolled in at the bottom are blank.
+    */
+    ScrollUp(u8),
 }
 
-// TODO as cast could fail, and second cannot be always confined to u4
+// Numeric tags for FFI front-ends that want to switch on the instruction type.
+// Tags are stable: existing values never change, new variants get the next free number.
+const TAG_NAMES: [&str; 42] = [
+    "System",
+    "ClearScreen",
+    "Return",
+    "Jump",
+    "Call",
+    "SkipEqual",
+    "SkipNotEqual",
+    "SkipEqualRegister",
+    "Load",
+    "Add",
+    "LoadRegister",
+    "OrRegister",
+    "AndRegister",
+    "XorRegister",
+    "AddRegister",
+    "SubRegister",
+    "ShrRegister",
+    "SubNotBorrowRegister",
+    "ShlRegister",
+    "SkipNotEqualRegister",
+    "Set",
+    "JumpV0",
+    "Random",
+    "Draw",
+    "SkipKey",
+    "SkipNotKey",
+    "LoadDelayTimer",
+    "LoadKey",
+    "SetDelayTimer",
+    "SetSoundTimer",
+    "AddI",
+    "LoadSprite",
+    "LoadBCD",
+    "SaveRegisters",
+    "LoadRegisters",
+    "ScrollDown",
+    "ScrollRight",
+    "ScrollLeft",
+    "LowRes",
+    "HighRes",
+    "LoadLargeSprite",
+    "ScrollUp",
+];
+
+// coarse groups of instructions, e.g. for VM::set_trace_filter
+// each group is one bit of a u16 mask, several are combined with |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCategory {
+    // SYS, JP, CALL, RET, JP V0 and the SE / SNE skips
+    FlowControl,
+    // LD, ADD, OR, AND, XOR, SUB, SHR, SUBN, SHL and RND on registers
+    Arithmetic,
+    // LD I, ADD I, LD F, LD HF, LD B and the register stores and loads
+    Memory,
+    // CLS, DRW, the scrolls, LOW and HIGH
+    Display,
+    // SKP, SKNP and LD Vx, K
+    Input,
+    // LD Vx, DT, LD DT, Vx and LD ST, Vx
+    Timer,
+}
+
+impl OpCategory {
+    pub const ALL: u16 = u16::MAX;
+
+    pub fn mask(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+impl OpCode {
+    pub const TAG_COUNT: u8 = TAG_NAMES.len() as u8;
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            OpCode::System(_) => 0,
+            OpCode::ClearScreen => 1,
+            OpCode::Return => 2,
+            OpCode::Jump(_) => 3,
+            OpCode::Call(_) => 4,
+            OpCode::SkipEqual(_, _) => 5,
+            OpCode::SkipNotEqual(_, _) => 6,
+            OpCode::SkipEqualRegister(_, _) => 7,
+            OpCode::Load(_, _) => 8,
+            OpCode::Add(_, _) => 9,
+            OpCode::LoadRegister(_, _) => 10,
+            OpCode::OrRegister(_, _) => 11,
+            OpCode::AndRegister(_, _) => 12,
+            OpCode::XorRegister(_, _) => 13,
+            OpCode::AddRegister(_, _) => 14,
+            OpCode::SubRegister(_, _) => 15,
+            OpCode::ShrRegister(_, _) => 16,
+            OpCode::SubNotBorrowRegister(_, _) => 17,
+            OpCode::ShlRegister(_, _) => 18,
+            OpCode::SkipNotEqualRegister(_, _) => 19,
+            OpCode::Set(_) => 20,
+            OpCode::JumpV0(_) => 21,
+            OpCode::Random(_, _) => 22,
+            OpCode::Draw(_, _, _) => 23,
+            OpCode::SkipKey(_) => 24,
+            OpCode::SkipNotKey(_) => 25,
+            OpCode::LoadDelayTimer(_) => 26,
+            OpCode::LoadKey(_) => 27,
+            OpCode::SetDelayTimer(_) => 28,
+            OpCode::SetSoundTimer(_) => 29,
+            OpCode::AddI(_) => 30,
+            OpCode::LoadSprite(_) => 31,
+            OpCode::LoadBCD(_) => 32,
+            OpCode::SaveRegisters(_) => 33,
+            OpCode::LoadRegisters(_) => 34,
+            OpCode::ScrollDown(_) => 35,
+            OpCode::ScrollRight => 36,
+            OpCode::ScrollLeft => 37,
+            OpCode::LowRes => 38,
+            OpCode::HighRes => 39,
+            OpCode::LoadLargeSprite(_) => 40,
+            OpCode::ScrollUp(_) => 41,
+        }
+    }
+
+    pub fn category(&self) -> OpCategory {
+        match self {
+            OpCode::System(_)
+            | OpCode::Return
+            | OpCode::Jump(_)
+            | OpCode::Call(_)
+            | OpCode::JumpV0(_)
+            | OpCode::SkipEqual(_, _)
+            | OpCode::SkipNotEqual(_, _)
+            | OpCode::SkipEqualRegister(_, _)
+            | OpCode::SkipNotEqualRegister(_, _) => OpCategory::FlowControl,
+            OpCode::Load(_, _)
+            | OpCode::Add(_, _)
+            | OpCode::LoadRegister(_, _)
+            | OpCode::OrRegister(_, _)
+            | OpCode::AndRegister(_, _)
+            | OpCode::XorRegister(_, _)
+            | OpCode::AddRegister(_, _)
+            | OpCode::SubRegister(_, _)
+            | OpCode::ShrRegister(_, _)
+            | OpCode::SubNotBorrowRegister(_, _)
+            | OpCode::ShlRegister(_, _)
+            | OpCode::Random(_, _) => OpCategory::Arithmetic,
+            OpCode::Set(_)
+            | OpCode::AddI(_)
+            | OpCode::LoadSprite(_)
+            | OpCode::LoadLargeSprite(_)
+            | OpCode::LoadBCD(_)
+            | OpCode::SaveRegisters(_)
+            | OpCode::LoadRegisters(_) => OpCategory::Memory,
+            OpCode::ClearScreen
+            | OpCode::Draw(_, _, _)
+            | OpCode::ScrollDown(_)
+            | OpCode::ScrollUp(_)
+            | OpCode::ScrollRight
+            | OpCode::ScrollLeft
+            | OpCode::LowRes
+            | OpCode::HighRes => OpCategory::Display,
+            OpCode::SkipKey(_) | OpCode::SkipNotKey(_) | OpCode::LoadKey(_) => OpCategory::Input,
+            OpCode::LoadDelayTimer(_) | OpCode::SetDelayTimer(_) | OpCode::SetSoundTimer(_) => {
+                OpCategory::Timer
+            }
+        }
+    }
+
+    // inverse of tag(): operands cannot be recovered, so only the variant name is returned
+    pub fn tag_name(tag: u8) -> Option<&'static str> {
+        TAG_NAMES.get(tag as usize).copied()
+    }
+
+    // Chip-8 has no real NOP, the canonical padding instruction is 0x0000 which decodes to SYS 0
+    // and is ignored like every other 0nnn
+    pub fn nop() -> OpCode {
+        OpCode::System(Addr(0))
+    }
+
+    // a program as it is stored in a ROM, every instruction is one big-endian word
+    pub fn encode_all(ops: &[OpCode]) -> Vec<u8> {
+        ops.iter()
+            .flat_map(|op| u16::from(op.clone()).to_be_bytes())
+            .collect()
+    }
+
+    // the one-line summary from the technical reference, for tooltips and teaching material
+    pub fn spec(&self) -> &'static str {
+        match self {
+            OpCode::System(_) => "Jump to a machine code routine at nnn.",
+            OpCode::ClearScreen => "Clear the display.",
+            OpCode::Return => "Return from a subroutine.",
+            OpCode::Jump(_) => "Jump to location nnn.",
+            OpCode::Call(_) => "Call subroutine at nnn.",
+            OpCode::SkipEqual(_, _) => "Skip next instruction if Vx = kk.",
+            OpCode::SkipNotEqual(_, _) => "Skip next instruction if Vx != kk.",
+            OpCode::SkipEqualRegister(_, _) => "Skip next instruction if Vx = Vy.",
+            OpCode::Load(_, _) => "Set Vx = kk.",
+            OpCode::Add(_, _) => "Set Vx = Vx + kk.",
+            OpCode::LoadRegister(_, _) => "Set Vx = Vy.",
+            OpCode::OrRegister(_, _) => "Set Vx = Vx OR Vy.",
+            OpCode::AndRegister(_, _) => "Set Vx = Vx AND Vy.",
+            OpCode::XorRegister(_, _) => "Set Vx = Vx XOR Vy.",
+            OpCode::AddRegister(_, _) => "Set Vx = Vx + Vy, set VF = carry.",
+            OpCode::SubRegister(_, _) => "Set Vx = Vx - Vy, set VF = NOT borrow.",
+            OpCode::ShrRegister(_, _) => "Set Vx = Vx SHR 1.",
+            OpCode::SubNotBorrowRegister(_, _) => "Set Vx = Vy - Vx, set VF = NOT borrow.",
+            OpCode::ShlRegister(_, _) => "Set Vx = Vx SHL 1.",
+            OpCode::SkipNotEqualRegister(_, _) => "Skip next instruction if Vx != Vy.",
+            OpCode::Set(_) => "Set I = nnn.",
+            OpCode::JumpV0(_) => "Jump to location nnn + V0.",
+            OpCode::Random(_, _) => "Set Vx = random byte AND kk.",
+            OpCode::Draw(_, _, _) => {
+                "Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision."
+            }
+            OpCode::SkipKey(_) => "Skip next instruction if key with the value of Vx is pressed.",
+            OpCode::SkipNotKey(_) => {
+                "Skip next instruction if key with the value of Vx is not pressed."
+            }
+            OpCode::LoadDelayTimer(_) => "Set Vx = delay timer value.",
+            OpCode::LoadKey(_) => "Wait for a key press, store the value of the key in Vx.",
+            OpCode::SetDelayTimer(_) => "Set delay timer = Vx.",
+            OpCode::SetSoundTimer(_) => "Set sound timer = Vx.",
+            OpCode::AddI(_) => "Set I = I + Vx.",
+            OpCode::LoadSprite(_) => "Set I = location of sprite for digit Vx.",
+            OpCode::LoadBCD(_) => {
+                "Store BCD representation of Vx in memory locations I, I+1, and I+2."
+            }
+            OpCode::SaveRegisters(_) => {
+                "Store registers V0 through Vx in memory starting at location I."
+            }
+            OpCode::LoadRegisters(_) => {
+                "Read registers V0 through Vx from memory starting at location I."
+            }
+            OpCode::ScrollDown(_) => "Scroll the display down by n pixels.",
+            OpCode::ScrollRight => "Scroll the display right by 4 pixels.",
+            OpCode::ScrollLeft => "Scroll the display left by 4 pixels.",
+            OpCode::LowRes => "Switch to the 64x32 low resolution display.",
+            OpCode::HighRes => "Switch to the 128x64 high resolution display.",
+            OpCode::LoadLargeSprite(_) => "Set I = location of the large sprite for digit Vx.",
+            OpCode::ScrollUp(_) => "Scroll the display up by n pixels.",
+        }
+    }
+}
 
 impl TryFrom<u16> for OpCode {
-    type Error = String; // todo std::err
+    type Error = DecodeError;
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
-        let first = (value >> (3 * 4)) as u8;
-        let second = ((value & 0x0100) >> (2 * 4)) as u8;
-        let thrid = ((value & 0x0010) >> 4) as u8;
-        let fourth: u16 = value & 0x001;
-        let fourth = fourth as u8;
+        let first = ((value & 0xF000) >> (3 * 4)) as u8;
+        let second = ((value & 0x0F00) >> (2 * 4)) as u8;
+        let thrid = ((value & 0x00F0) >> 4) as u8;
+        let fourth = (value & 0x000F) as u8;
 
-        let nnn = value & 0x0111;
-        let kk = (value & 0x0011) as u8;
+        let nnn = value & 0x0FFF;
+        let kk = (value & 0x00FF) as u8;
 
         match first {
             0x00 => {
@@ -237,6 +548,18 @@ impl TryFrom<u16> for OpCode {
                     return Ok(OpCode::ClearScreen);
                 } else if value == 0x00EE {
                     return Ok(OpCode::Return);
+                } else if value & 0xFFF0 == 0x00C0 {
+                    return Ok(OpCode::ScrollDown(fourth));
+                } else if value & 0xFFF0 == 0x00D0 {
+                    return Ok(OpCode::ScrollUp(fourth));
+                } else if value == 0x00FB {
+                    return Ok(OpCode::ScrollRight);
+                } else if value == 0x00FC {
+                    return Ok(OpCode::ScrollLeft);
+                } else if value == 0x00FE {
+                    return Ok(OpCode::LowRes);
+                } else if value == 0x00FF {
+                    return Ok(OpCode::HighRes);
                 }
                 Ok(OpCode::System(Addr(nnn)))
             }
@@ -246,7 +569,7 @@ impl TryFrom<u16> for OpCode {
             0x04 => Ok(OpCode::SkipNotEqual(V(second), Byte(kk))),
             0x05 => {
                 if fourth != 0 {
-                    return Err("fourth not zero".to_owned());
+                    return Err(DecodeError::ReservedBitsSet(value));
                 }
                 Ok(OpCode::SkipEqualRegister(V(second), V(thrid)))
             }
@@ -262,11 +585,11 @@ impl TryFrom<u16> for OpCode {
                 0x06 => Ok(OpCode::ShrRegister(V(second), V(thrid))),
                 0x07 => Ok(OpCode::SubNotBorrowRegister(V(second), V(thrid))),
                 0x0E => Ok(OpCode::ShlRegister(V(second), V(thrid))),
-                _ => Err("0x8xx fail".to_owned()),
+                _ => Err(DecodeError::UnknownOpcode(value)),
             },
             0x09 => {
                 if fourth != 0 {
-                    return Err("fourth is not zero".to_owned());
+                    return Err(DecodeError::ReservedBitsSet(value));
                 }
                 Ok(OpCode::SkipNotEqualRegister(V(second), V(thrid)))
             }
@@ -277,7 +600,7 @@ impl TryFrom<u16> for OpCode {
             0x0e => match kk {
                 0x9e => Ok(OpCode::SkipKey(V(second))),
                 0xa1 => Ok(OpCode::SkipNotKey(V(second))),
-                _ => Err("neither Ex9E nor ExA1".to_owned()),
+                _ => Err(DecodeError::UnknownOpcode(value)),
             },
             0x0f => match kk {
                 0x07 => Ok(OpCode::LoadDelayTimer(V(second))),
@@ -286,12 +609,13 @@ impl TryFrom<u16> for OpCode {
                 0x18 => Ok(OpCode::SetSoundTimer(V(second))),
                 0x1e => Ok(OpCode::AddI(V(second))),
                 0x29 => Ok(OpCode::LoadSprite(V(second))),
+                0x30 => Ok(OpCode::LoadLargeSprite(V(second))),
                 0x33 => Ok(OpCode::LoadBCD(V(second))),
                 0x55 => Ok(OpCode::SaveRegisters(V(second))),
                 0x65 => Ok(OpCode::LoadRegisters(V(second))),
-                _ => Err("0xfxx fail".to_owned()),
+                _ => Err(DecodeError::UnknownFx(kk)),
             },
-            _ => Err("first value fail".to_owned()),
+            _ => Err(DecodeError::UnknownOpcode(value)),
         }
     }
 }
@@ -299,93 +623,496 @@ impl TryFrom<u16> for OpCode {
 impl Into<String> for OpCode {
     fn into(self) -> String {
         match self {
-            OpCode::System(nnn) => format!("SYS {}", nnn.0),
+            OpCode::System(nnn) => format!("SYS {}", nnn),
             OpCode::ClearScreen => "CLS".to_owned(),
             OpCode::Return => "RET".to_owned(),
-            OpCode::Jump(nnn) => format!("JP {}", nnn.0),
-            OpCode::Call(nnn) => format!("CALL {}", nnn.0),
-            OpCode::SkipEqual(x, kk) => format!("SE V{} {}", x.0, kk.0),
-            OpCode::SkipNotEqual(x, kk) => format!("SNE V{} {}", x.0, kk.0),
-            OpCode::SkipEqualRegister(x, y) => format!("SN V{} V{}", x.0, y.0),
-            OpCode::Load(x, kk) => format!("LD V{} {}", x.0, kk.0),
-            OpCode::Add(x, kk) => format!("ADD V{} {}", x.0, kk.0),
-            OpCode::LoadRegister(x, y) => format!("LD V{} V{}", x.0, y.0),
-            OpCode::OrRegister(x, y) => format!("OR V{} V{}", x.0, y.0),
-            OpCode::AndRegister(x, y) => format!("AND V{} V{}", x.0, y.0),
-            OpCode::XorRegister(x, y) => format!("XOR V{} V{}", x.0, y.0),
-            OpCode::AddRegister(x, y) => format!("ADD V{} V{}", x.0, y.0),
-            OpCode::SubRegister(x, y) => format!("SUB V{} V{}", x.0, y.0),
-            OpCode::ShrRegister(x, y) => format!("SHR V{} V{}", x.0, y.0),
-            OpCode::SubNotBorrowRegister(x, y) => format!("SUBN V{} V{}", x.0, y.0),
-            OpCode::ShlRegister(x, y) => format!("SHL V{} {{, V{}}}", x.0, y.0),
-            OpCode::SkipNotEqualRegister(x, y) => format!("SNE V{}, V{}", x.0, y.0),
-            OpCode::Set(nnn) => format!("LD I, {}", nnn.0),
-            OpCode::JumpV0(nnn) => format!("JP V0, {}", nnn.0),
-            OpCode::Random(x, kk) => format!("RND V{}, {}", x.0, kk.0),
-            OpCode::Draw(x, y, nibble) => format!("DRW V{}, V{}, {}", x.0, y.0, nibble),
-            OpCode::SkipKey(x) => format!("SKP V{}", x.0),
-            OpCode::SkipNotKey(x) => format!("SKNP V{}", x.0),
-            OpCode::LoadDelayTimer(x) => format!("LD V{}, DT", x.0),
-            OpCode::LoadKey(x) => format!("LD V{}, K", x.0),
-            OpCode::SetDelayTimer(x) => format!("LD DT, V{}", x.0),
-            OpCode::SetSoundTimer(x) => format!("LD ST, V{}", x.0),
-            OpCode::AddI(x) => format!("ADD I, V{}", x.0),
-            OpCode::LoadSprite(x) => format!("LD F, V{}", x.0),
-            OpCode::LoadBCD(x) => format!("LD B, V{}", x.0),
-            OpCode::SaveRegisters(x) => format!("LD [I], V{}", x.0),
-            OpCode::LoadRegisters(x) => format!("LD V{}, [I]", x.0),
+            OpCode::Jump(nnn) => format!("JP {}", nnn),
+            OpCode::Call(nnn) => format!("CALL {}", nnn),
+            OpCode::SkipEqual(x, kk) => format!("SE {}, {}", x, kk),
+            OpCode::SkipNotEqual(x, kk) => format!("SNE {}, {}", x, kk),
+            OpCode::SkipEqualRegister(x, y) => format!("SE {}, {}", x, y),
+            OpCode::Load(x, kk) => format!("LD {}, {}", x, kk),
+            OpCode::Add(x, kk) => format!("ADD {}, {}", x, kk),
+            OpCode::LoadRegister(x, y) => format!("LD {}, {}", x, y),
+            OpCode::OrRegister(x, y) => format!("OR {}, {}", x, y),
+            OpCode::AndRegister(x, y) => format!("AND {}, {}", x, y),
+            OpCode::XorRegister(x, y) => format!("XOR {}, {}", x, y),
+            OpCode::AddRegister(x, y) => format!("ADD {}, {}", x, y),
+            OpCode::SubRegister(x, y) => format!("SUB {}, {}", x, y),
+            OpCode::ShrRegister(x, y) => format!("SHR {}, {}", x, y),
+            OpCode::SubNotBorrowRegister(x, y) => format!("SUBN {}, {}", x, y),
+            OpCode::ShlRegister(x, y) => format!("SHL {}, {}", x, y),
+            OpCode::SkipNotEqualRegister(x, y) => format!("SNE {}, {}", x, y),
+            OpCode::Set(nnn) => format!("LD I, {}", nnn),
+            OpCode::JumpV0(nnn) => format!("JP V0, {}", nnn),
+            OpCode::Random(x, kk) => format!("RND {}, {}", x, kk),
+            OpCode::Draw(x, y, nibble) => format!("DRW {}, {}, {}", x, y, nibble),
+            OpCode::SkipKey(x) => format!("SKP {}", x),
+            OpCode::SkipNotKey(x) => format!("SKNP {}", x),
+            OpCode::LoadDelayTimer(x) => format!("LD {}, DT", x),
+            OpCode::LoadKey(x) => format!("LD {}, K", x),
+            OpCode::SetDelayTimer(x) => format!("LD DT, {}", x),
+            OpCode::SetSoundTimer(x) => format!("LD ST, {}", x),
+            OpCode::AddI(x) => format!("ADD I, {}", x),
+            OpCode::LoadSprite(x) => format!("LD F, {}", x),
+            OpCode::LoadBCD(x) => format!("LD B, {}", x),
+            OpCode::SaveRegisters(x) => format!("LD [I], {}", x),
+            OpCode::LoadRegisters(x) => format!("LD {}, [I]", x),
+            OpCode::ScrollDown(nibble) => format!("SCD {}", nibble),
+            OpCode::ScrollRight => "SCR".to_owned(),
+            OpCode::ScrollLeft => "SCL".to_owned(),
+            OpCode::LowRes => "LOW".to_owned(),
+            OpCode::HighRes => "HIGH".to_owned(),
+            OpCode::LoadLargeSprite(x) => format!("LD HF, {}", x),
+            OpCode::ScrollUp(nibble) => format!("SCU {}", nibble),
         }
     }
 }
 
-// Do not create these yet
-
-// impl Into<u16> for OpCode {
-//     fn into(self) -> u16 {
-//         match self {
-//             OpCode::System(addr) => todo!(),
-//             OpCode::ClearScreen => 0x00E0,
-//             OpCode::Return => 0x00EE,
-//             OpCode::Jump(addr) => todo!(),
-//             OpCode::Call(addr) => todo!(),
-//             OpCode::SkipEqual(x, kk) => todo!(),
-//             OpCode::SkipNotEqual(x, kk) => todo!(),
-//             OpCode::SkipEqualRegister(x, y) => todo!(),
-//             OpCode::Load(x, kk) => todo!(),
-//             OpCode::Add(x, kk) => todo!(),
-//             OpCode::LoadRegister(x, y) => todo!(),
-//             OpCode::OrRegister(x, y) => todo!(),
-//             OpCode::AndRegister(x, y) => todo!(),
-//             OpCode::XorRegister(x, y) => todo!(),
-//             OpCode::AddRegister(x, y) => todo!(),
-//             OpCode::SubRegister(x, y) => todo!(),
-//             OpCode::ShrRegister(x, y) => todo!(),
-//             OpCode::SubNotBorrowRegister(x, y) => todo!(),
-//             OpCode::ShlRegister(x, y) => todo!(),
-//             OpCode::SkipNotEqualRegister(x, y) => todo!(),
-//             OpCode::Set(addr) => todo!(),
-//             OpCode::JumpV0(addr) => todo!(),
-//             OpCode::Random(x, kk) => todo!(),
-//             OpCode::Draw(x, y, nibble) => todo!(),
-//             OpCode::SkipKey(x) => todo!(),
-//             OpCode::SkipNotKey(x) => todo!(),
-//             OpCode::LoadDelayTimer(x) => todo!(),
-//             OpCode::LoadKey(x) => todo!(),
-//             OpCode::SetDelayTimer(x) => todo!(),
-//             OpCode::SetSoundTimer(x) => todo!(),
-//             OpCode::AddI(x) => todo!(),
-//             OpCode::LoadSprite(x) => todo!(),
-//             OpCode::LoadBCD(x) => todo!(),
-//             OpCode::SaveRegisters(x) => todo!(),
-//             OpCode::LoadRegisters(x) => todo!(),
-//         }
-//     }
-// }
-
-// impl TryFrom<&str> for OpCode {
-//     type Error = String; // todo std::err
-
-//     fn try_from(value: &str) -> Result<Self, Self::Error> {
-//         todo!()
-//     }
-// }
+fn encode_nnn(nnn: Addr) -> u16 {
+    nnn.0 & 0x0FFF
+}
+
+fn encode_x(x: V) -> u16 {
+    (x.0 as u16 & 0x0F) << 8
+}
+
+fn encode_xy(x: V, y: V) -> u16 {
+    encode_x(x) | ((y.0 as u16 & 0x0F) << 4)
+}
+
+fn encode_xkk(x: V, kk: Byte) -> u16 {
+    encode_x(x) | kk.0 as u16
+}
+
+// the inverse of TryFrom<u16>, operands wider than their field are truncated
+impl From<OpCode> for u16 {
+    fn from(op: OpCode) -> u16 {
+        match op {
+            OpCode::System(nnn) => encode_nnn(nnn),
+            OpCode::ClearScreen => 0x00E0,
+            OpCode::Return => 0x00EE,
+            OpCode::Jump(nnn) => 0x1000 | encode_nnn(nnn),
+            OpCode::Call(nnn) => 0x2000 | encode_nnn(nnn),
+            OpCode::SkipEqual(x, kk) => 0x3000 | encode_xkk(x, kk),
+            OpCode::SkipNotEqual(x, kk) => 0x4000 | encode_xkk(x, kk),
+            OpCode::SkipEqualRegister(x, y) => 0x5000 | encode_xy(x, y),
+            OpCode::Load(x, kk) => 0x6000 | encode_xkk(x, kk),
+            OpCode::Add(x, kk) => 0x7000 | encode_xkk(x, kk),
+            OpCode::LoadRegister(x, y) => 0x8000 | encode_xy(x, y),
+            OpCode::OrRegister(x, y) => 0x8001 | encode_xy(x, y),
+            OpCode::AndRegister(x, y) => 0x8002 | encode_xy(x, y),
+            OpCode::XorRegister(x, y) => 0x8003 | encode_xy(x, y),
+            OpCode::AddRegister(x, y) => 0x8004 | encode_xy(x, y),
+            OpCode::SubRegister(x, y) => 0x8005 | encode_xy(x, y),
+            OpCode::ShrRegister(x, y) => 0x8006 | encode_xy(x, y),
+            OpCode::SubNotBorrowRegister(x, y) => 0x8007 | encode_xy(x, y),
+            OpCode::ShlRegister(x, y) => 0x800E | encode_xy(x, y),
+            OpCode::SkipNotEqualRegister(x, y) => 0x9000 | encode_xy(x, y),
+            OpCode::Set(nnn) => 0xA000 | encode_nnn(nnn),
+            OpCode::JumpV0(nnn) => 0xB000 | encode_nnn(nnn),
+            OpCode::Random(x, kk) => 0xC000 | encode_xkk(x, kk),
+            OpCode::Draw(x, y, nibble) => 0xD000 | encode_xy(x, y) | (nibble as u16 & 0x0F),
+            OpCode::SkipKey(x) => 0xE09E | encode_x(x),
+            OpCode::SkipNotKey(x) => 0xE0A1 | encode_x(x),
+            OpCode::LoadDelayTimer(x) => 0xF007 | encode_x(x),
+            OpCode::LoadKey(x) => 0xF00A | encode_x(x),
+            OpCode::SetDelayTimer(x) => 0xF015 | encode_x(x),
+            OpCode::SetSoundTimer(x) => 0xF018 | encode_x(x),
+            OpCode::AddI(x) => 0xF01E | encode_x(x),
+            OpCode::LoadSprite(x) => 0xF029 | encode_x(x),
+            OpCode::LoadBCD(x) => 0xF033 | encode_x(x),
+            OpCode::SaveRegisters(x) => 0xF055 | encode_x(x),
+            OpCode::LoadRegisters(x) => 0xF065 | encode_x(x),
+            OpCode::ScrollDown(nibble) => 0x00C0 | (nibble as u16 & 0x0F),
+            OpCode::ScrollRight => 0x00FB,
+            OpCode::ScrollLeft => 0x00FC,
+            OpCode::LowRes => 0x00FE,
+            OpCode::HighRes => 0x00FF,
+            OpCode::LoadLargeSprite(x) => 0xF030 | encode_x(x),
+            OpCode::ScrollUp(nibble) => 0x00D0 | (nibble as u16 & 0x0F),
+        }
+    }
+}
+
+// the inverse of Into<String>, e.g. "DRW V1, V2, 5" or "LD V10 35"
+// numbers are decimal or 0x-prefixed hex, registers are VA or V10, commas are optional
+impl TryFrom<&str> for OpCode {
+    type Error = AssembleError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let cleaned: String = value
+            .chars()
+            .map(|c| if matches!(c, ',' | '{' | '}') { ' ' } else { c })
+            .collect();
+        let tokens: Vec<String> = cleaned
+            .split_whitespace()
+            .map(|t| t.to_ascii_uppercase())
+            .collect();
+        let unknown = || AssembleError::UnknownInstruction(value.trim().to_owned());
+        let (mnemonic, operands) = tokens.split_first().ok_or_else(unknown)?;
+        let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+
+        let op = match (mnemonic.as_str(), operands.as_slice()) {
+            ("CLS", []) => OpCode::ClearScreen,
+            ("RET", []) => OpCode::Return,
+            ("SYS", [nnn]) => OpCode::System(parse_addr(nnn)?),
+            ("JP", ["V0", nnn]) => OpCode::JumpV0(parse_addr(nnn)?),
+            ("JP", [nnn]) => OpCode::Jump(parse_addr(nnn)?),
+            ("CALL", [nnn]) => OpCode::Call(parse_addr(nnn)?),
+            ("SE", [x, y]) if is_register(y) => OpCode::SkipEqualRegister(parse_v(x)?, parse_v(y)?),
+            ("SE", [x, kk]) => OpCode::SkipEqual(parse_v(x)?, parse_byte(kk)?),
+            ("SNE", [x, y]) if is_register(y) => {
+                OpCode::SkipNotEqualRegister(parse_v(x)?, parse_v(y)?)
+            }
+            ("SNE", [x, kk]) => OpCode::SkipNotEqual(parse_v(x)?, parse_byte(kk)?),
+            ("LD", ["I", nnn]) => OpCode::Set(parse_addr(nnn)?),
+            ("LD", ["DT", x]) => OpCode::SetDelayTimer(parse_v(x)?),
+            ("LD", ["ST", x]) => OpCode::SetSoundTimer(parse_v(x)?),
+            ("LD", ["F", x]) => OpCode::LoadSprite(parse_v(x)?),
+            ("LD", ["HF", x]) => OpCode::LoadLargeSprite(parse_v(x)?),
+            ("LD", ["B", x]) => OpCode::LoadBCD(parse_v(x)?),
+            ("LD", ["[I]", x]) => OpCode::SaveRegisters(parse_v(x)?),
+            ("LD", [x, "DT"]) => OpCode::LoadDelayTimer(parse_v(x)?),
+            ("LD", [x, "K"]) => OpCode::LoadKey(parse_v(x)?),
+            ("LD", [x, "[I]"]) => OpCode::LoadRegisters(parse_v(x)?),
+            ("LD", [x, y]) if is_register(y) => OpCode::LoadRegister(parse_v(x)?, parse_v(y)?),
+            ("LD", [x, kk]) => OpCode::Load(parse_v(x)?, parse_byte(kk)?),
+            ("ADD", ["I", x]) => OpCode::AddI(parse_v(x)?),
+            ("ADD", [x, y]) if is_register(y) => OpCode::AddRegister(parse_v(x)?, parse_v(y)?),
+            ("ADD", [x, kk]) => OpCode::Add(parse_v(x)?, parse_byte(kk)?),
+            ("OR", [x, y]) => OpCode::OrRegister(parse_v(x)?, parse_v(y)?),
+            ("AND", [x, y]) => OpCode::AndRegister(parse_v(x)?, parse_v(y)?),
+            ("XOR", [x, y]) => OpCode::XorRegister(parse_v(x)?, parse_v(y)?),
+            ("SUB", [x, y]) => OpCode::SubRegister(parse_v(x)?, parse_v(y)?),
+            ("SUBN", [x, y]) => OpCode::SubNotBorrowRegister(parse_v(x)?, parse_v(y)?),
+            // Vy is optional for the shifts, as in "SHR Vx {, Vy}", and defaults to Vx
+            // so the instruction shifts Vx in place under either shift quirk
+            ("SHR", [x]) => OpCode::ShrRegister(parse_v(x)?, parse_v(x)?),
+            ("SHR", [x, y]) => OpCode::ShrRegister(parse_v(x)?, parse_v(y)?),
+            ("SHL", [x]) => OpCode::ShlRegister(parse_v(x)?, parse_v(x)?),
+            ("SHL", [x, y]) => OpCode::ShlRegister(parse_v(x)?, parse_v(y)?),
+            ("RND", [x, kk]) => OpCode::Random(parse_v(x)?, parse_byte(kk)?),
+            ("DRW", [x, y, n]) => OpCode::Draw(parse_v(x)?, parse_v(y)?, parse_nibble(n)?),
+            ("SKP", [x]) => OpCode::SkipKey(parse_v(x)?),
+            ("SKNP", [x]) => OpCode::SkipNotKey(parse_v(x)?),
+            ("SCD", [n]) => OpCode::ScrollDown(parse_nibble(n)?),
+            ("SCU", [n]) => OpCode::ScrollUp(parse_nibble(n)?),
+            ("SCR", []) => OpCode::ScrollRight,
+            ("SCL", []) => OpCode::ScrollLeft,
+            ("LOW", []) => OpCode::LowRes,
+            ("HIGH", []) => OpCode::HighRes,
+            _ => return Err(unknown()),
+        };
+        Ok(op)
+    }
+}
+
+fn is_register(token: &str) -> bool {
+    parse_v(token).is_ok()
+}
+
+fn parse_number(token: &str, max: u16) -> Result<u16, AssembleError> {
+    let n = match token.strip_prefix("0X") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => token.parse::<u16>(),
+    }
+    .map_err(|_| AssembleError::InvalidOperand(token.to_owned()))?;
+    if n > max {
+        return Err(AssembleError::OperandOutOfRange(token.to_owned()));
+    }
+    Ok(n)
+}
+
+fn parse_addr(token: &str) -> Result<Addr, AssembleError> {
+    parse_number(token, 0x0FFF).map(Addr)
+}
+
+fn parse_byte(token: &str) -> Result<Byte, AssembleError> {
+    parse_number(token, 0xFF).map(|n| Byte(n as u8))
+}
+
+fn parse_nibble(token: &str) -> Result<u8, AssembleError> {
+    parse_number(token, 0x0F).map(|n| n as u8)
+}
+
+// a single hex digit as in VA, or a decimal index as in V10
+fn parse_v(token: &str) -> Result<V, AssembleError> {
+    let invalid = || AssembleError::InvalidOperand(token.to_owned());
+    let index = token.strip_prefix('V').ok_or_else(invalid)?;
+    let n = match index.len() {
+        1 => u8::from_str_radix(index, 16).map_err(|_| invalid())?,
+        _ => index.parse::<u8>().map_err(|_| invalid())?,
+    };
+    if n > 0x0F {
+        return Err(AssembleError::OperandOutOfRange(token.to_owned()));
+    }
+    Ok(V(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one of every variant, in tag order
+    fn every_variant() -> Vec<OpCode> {
+        vec![
+            OpCode::System(Addr(0x123)),
+            OpCode::ClearScreen,
+            OpCode::Return,
+            OpCode::Jump(Addr(0x200)),
+            OpCode::Call(Addr(0x2F0)),
+            OpCode::SkipEqual(V(1), Byte(0x23)),
+            OpCode::SkipNotEqual(V(2), Byte(0x34)),
+            OpCode::SkipEqualRegister(V(3), V(4)),
+            OpCode::Load(V(0xA), Byte(0x23)),
+            OpCode::Add(V(5), Byte(0xFF)),
+            OpCode::LoadRegister(V(6), V(7)),
+            OpCode::OrRegister(V(8), V(9)),
+            OpCode::AndRegister(V(0xA), V(0xB)),
+            OpCode::XorRegister(V(0xC), V(0xD)),
+            OpCode::AddRegister(V(0xE), V(0xF)),
+            OpCode::SubRegister(V(0), V(1)),
+            OpCode::ShrRegister(V(2), V(3)),
+            OpCode::SubNotBorrowRegister(V(4), V(5)),
+            OpCode::ShlRegister(V(6), V(7)),
+            OpCode::SkipNotEqualRegister(V(8), V(9)),
+            OpCode::Set(Addr(0x050)),
+            OpCode::JumpV0(Addr(0x300)),
+            OpCode::Random(V(0xA), Byte(0x0F)),
+            OpCode::Draw(V(1), V(2), 5),
+            OpCode::SkipKey(V(3)),
+            OpCode::SkipNotKey(V(4)),
+            OpCode::LoadDelayTimer(V(5)),
+            OpCode::LoadKey(V(6)),
+            OpCode::SetDelayTimer(V(7)),
+            OpCode::SetSoundTimer(V(8)),
+            OpCode::AddI(V(9)),
+            OpCode::LoadSprite(V(0xA)),
+            OpCode::LoadBCD(V(0xB)),
+            OpCode::SaveRegisters(V(0xC)),
+            OpCode::LoadRegisters(V(0xD)),
+            OpCode::ScrollDown(4),
+            OpCode::ScrollRight,
+            OpCode::ScrollLeft,
+            OpCode::LowRes,
+            OpCode::HighRes,
+            OpCode::LoadLargeSprite(V(0xE)),
+            OpCode::ScrollUp(3),
+        ]
+    }
+
+    #[test]
+    fn tags_are_stable_and_unique() {
+        assert_eq!(OpCode::System(Addr(0)).tag(), 0);
+        assert_eq!(OpCode::ClearScreen.tag(), 1);
+        assert_eq!(OpCode::Draw(V(0), V(0), 0).tag(), 23);
+        assert_eq!(OpCode::LoadRegisters(V(0)).tag(), 34);
+
+        let tags: Vec<u8> = every_variant().iter().map(OpCode::tag).collect();
+        assert_eq!(tags, (0..OpCode::TAG_COUNT).collect::<Vec<u8>>());
+        assert_eq!(OpCode::tag_name(23), Some("Draw"));
+        assert_eq!(OpCode::tag_name(OpCode::TAG_COUNT), None);
+    }
+
+    #[test]
+    fn same_register_shifts_keep_y_equal_to_x() {
+        for word in [0x8336, 0x833E] {
+            let op = OpCode::try_from(word).unwrap();
+            assert!(matches!(
+                op,
+                OpCode::ShrRegister(V(3), V(3)) | OpCode::ShlRegister(V(3), V(3))
+            ));
+            assert_eq!(u16::from(op), word);
+        }
+    }
+
+    #[test]
+    fn encode_all_round_trips_through_the_disassembler() {
+        use crate::interp::disasm::Disassembler;
+
+        let program = vec![
+            OpCode::ClearScreen,
+            OpCode::Load(V(0), Byte(0x0A)),
+            OpCode::Set(Addr(0x050)),
+            OpCode::Draw(V(0), V(0), 5),
+            OpCode::Jump(Addr(0x208)),
+        ];
+        let bytes = OpCode::encode_all(&program);
+        assert_eq!(
+            bytes,
+            [0x00, 0xE0, 0x60, 0x0A, 0xA0, 0x50, 0xD0, 0x05, 0x12, 0x08]
+        );
+        let decoded: Vec<OpCode> = Disassembler::new(&bytes, 0x200)
+            .map(|(_, _, op)| op.unwrap())
+            .collect();
+        assert_eq!(decoded, program);
+    }
+
+    #[test]
+    fn unknown_ex_and_fx_words_are_decode_errors() {
+        assert_eq!(
+            OpCode::try_from(0xE000),
+            Err(DecodeError::UnknownOpcode(0xE000))
+        );
+        assert_eq!(
+            OpCode::try_from(0xE3A2),
+            Err(DecodeError::UnknownOpcode(0xE3A2))
+        );
+        assert_eq!(OpCode::try_from(0xF099), Err(DecodeError::UnknownFx(0x99)));
+        assert_eq!(OpCode::try_from(0xE39E), Ok(OpCode::SkipKey(V(3))));
+        assert_eq!(OpCode::try_from(0xF365), Ok(OpCode::LoadRegisters(V(3))));
+    }
+
+    #[test]
+    fn decodes_operands_from_the_right_nibbles() {
+        let table = [
+            (0x00E0, OpCode::ClearScreen),
+            (0x1ABC, OpCode::Jump(Addr(0xABC))),
+            (0x2F0E, OpCode::Call(Addr(0xF0E))),
+            (0x3A5C, OpCode::SkipEqual(V(0xA), Byte(0x5C))),
+            (0x5BC0, OpCode::SkipEqualRegister(V(0xB), V(0xC))),
+            (0x6A23, OpCode::Load(V(0xA), Byte(0x23))),
+            (0x7F01, OpCode::Add(V(0xF), Byte(0x01))),
+            (0x8CD4, OpCode::AddRegister(V(0xC), V(0xD))),
+            (0x9120, OpCode::SkipNotEqualRegister(V(1), V(2))),
+            (0xA123, OpCode::Set(Addr(0x123))),
+            (0xB3FF, OpCode::JumpV0(Addr(0x3FF))),
+            (0xC70F, OpCode::Random(V(7), Byte(0x0F))),
+            (0xD125, OpCode::Draw(V(1), V(2), 5)),
+            (0xE4A1, OpCode::SkipNotKey(V(4))),
+            (0xF933, OpCode::LoadBCD(V(9))),
+        ];
+        for (word, op) in table {
+            assert_eq!(OpCode::try_from(word), Ok(op), "{:#06x}", word);
+        }
+    }
+
+    #[test]
+    fn every_decodable_word_re_encodes_to_itself() {
+        // decoding is strict about reserved bits, so there are no don't-care bits to mask off
+        let mut decoded = 0;
+        for word in 0..=u16::MAX {
+            if let Ok(op) = OpCode::try_from(word) {
+                assert_eq!(u16::from(op), word, "{:#06x}", word);
+                decoded += 1;
+            }
+        }
+        // 0nnn, 1nnn - 4xkk, 6xkk, 7xkk and Annn - Dxyn take every operand: 11 * 4096
+        // 5xy0 and 9xy0: 2 * 256, the nine 8xy_ forms: 9 * 256, two Ex and ten Fx forms: 12 * 16
+        assert_eq!(decoded, 11 * 4096 + 2 * 256 + 9 * 256 + 12 * 16);
+    }
+
+    #[test]
+    fn every_variant_round_trips_through_its_mnemonic() {
+        for op in every_variant() {
+            let text: String = op.clone().into();
+            assert_eq!(OpCode::try_from(text.as_str()), Ok(op), "{}", text);
+        }
+    }
+
+    #[test]
+    fn assembler_accepts_loose_spellings() {
+        let cases = [
+            ("LD V10 35", OpCode::Load(V(0xA), Byte(35))),
+            ("drw v1, v2, 0x5", OpCode::Draw(V(1), V(2), 5)),
+            ("JP 512", OpCode::Jump(Addr(0x200))),
+            ("  CLS  ", OpCode::ClearScreen),
+            ("SHR V3", OpCode::ShrRegister(V(3), V(3))),
+            ("SHL VA", OpCode::ShlRegister(V(0xA), V(0xA))),
+        ];
+        for (text, op) in cases {
+            assert_eq!(OpCode::try_from(text), Ok(op), "{}", text);
+        }
+    }
+
+    #[test]
+    fn assembler_rejects_garbage() {
+        assert_eq!(
+            OpCode::try_from("FOO V1"),
+            Err(AssembleError::UnknownInstruction("FOO V1".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from(""),
+            Err(AssembleError::UnknownInstruction(String::new()))
+        );
+        assert_eq!(
+            OpCode::try_from("CLS V1"),
+            Err(AssembleError::UnknownInstruction("CLS V1".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from("LD VG, 1"),
+            Err(AssembleError::InvalidOperand("VG".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from("LD V16, 1"),
+            Err(AssembleError::OperandOutOfRange("V16".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from("LD V1, 256"),
+            Err(AssembleError::OperandOutOfRange("256".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from("JP 0x1000"),
+            Err(AssembleError::OperandOutOfRange("0X1000".to_owned()))
+        );
+        assert_eq!(
+            OpCode::try_from("DRW V1, V2, five"),
+            Err(AssembleError::InvalidOperand("FIVE".to_owned()))
+        );
+    }
+
+    #[test]
+    fn spec_summarises_each_variant() {
+        assert!(OpCode::ClearScreen.spec().contains("Clear the display"));
+        assert_eq!(
+            OpCode::Draw(V(1), V(2), 5).spec(),
+            OpCode::Draw(V(0), V(0), 0).spec()
+        );
+        assert!(every_variant().iter().all(|op| !op.spec().is_empty()));
+    }
+
+    #[test]
+    fn u16_from_opcode_encodes_canonical_words() {
+        assert_eq!(u16::from(OpCode::Return), 0x00EE);
+        assert_eq!(u16::from(OpCode::ClearScreen), 0x00E0);
+        assert_eq!(u16::from(OpCode::Draw(V(1), V(2), 5)), 0xD125);
+        assert_eq!(u16::from(OpCode::Load(V(0xA), Byte(0x23))), 0x6A23);
+        let word: u16 = OpCode::Jump(Addr(0x2F0)).into();
+        assert_eq!(word, 0x12F0);
+    }
+
+    #[test]
+    fn operands_format_as_hex() {
+        let text: String = OpCode::Draw(V(0xA), V(0xB), 0xF).into();
+        assert_eq!(text, "DRW VA, VB, 15");
+        assert_eq!(Byte(0x0A).to_string(), "0x0A");
+        assert_eq!(Addr(0x2A).to_string(), "0x02A");
+        assert_eq!(V(0xF).to_string(), "VF");
+    }
+
+    #[test]
+    fn decode_errors_name_what_is_wrong() {
+        assert_eq!(
+            OpCode::try_from(0x5121),
+            Err(DecodeError::ReservedBitsSet(0x5121))
+        );
+        assert_eq!(
+            OpCode::try_from(0x9AB1),
+            Err(DecodeError::ReservedBitsSet(0x9AB1))
+        );
+        assert_eq!(
+            OpCode::try_from(0x812F),
+            Err(DecodeError::UnknownOpcode(0x812F))
+        );
+        assert_eq!(OpCode::try_from(0xF099), Err(DecodeError::UnknownFx(0x99)));
+    }
+
+    #[test]
+    fn skip_equal_register_prints_as_se() {
+        let text: String = OpCode::SkipEqualRegister(V(1), V(2)).into();
+        assert_eq!(text, "SE V1, V2");
+        let text: String = OpCode::SkipNotEqualRegister(V(1), V(2)).into();
+        assert_eq!(text, "SNE V1, V2");
+    }
+}
@@ -0,0 +1,44 @@
+use random::Source;
+
+// source of the random bytes consumed by Cxkk - RND Vx, byte
+pub trait Chip8Rng: Chip8RngClone {
+    fn next_u8(&mut self) -> u8;
+}
+
+// lets VM keep deriving Clone while it holds a boxed rng
+pub trait Chip8RngClone {
+    fn clone_box(&self) -> Box<dyn Chip8Rng>;
+}
+
+impl<T: 'static + Chip8Rng + Clone> Chip8RngClone for T {
+    fn clone_box(&self) -> Box<dyn Chip8Rng> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Chip8Rng> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl Chip8Rng for random::Xorshift128Plus {
+    fn next_u8(&mut self) -> u8 {
+        self.read::<u8>()
+    }
+}
+
+// replays the given bytes in a loop, handy for asserting exact Cxkk results
+#[derive(Clone)]
+pub struct FixedSequence(pub Vec<u8>);
+
+impl Chip8Rng for FixedSequence {
+    fn next_u8(&mut self) -> u8 {
+        if self.0.is_empty() {
+            return 0;
+        }
+        let b = self.0.remove(0);
+        self.0.push(b);
+        b
+    }
+}
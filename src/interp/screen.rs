@@ -0,0 +1,85 @@
+// where the pixels of CLS, DRW and the SUPER-CHIP scrolls end up, e.g. an SDL texture or a web canvas
+// VM keeps its own frame buffer for collisions and frame_buffer(), the screen is told about every pixel that changes
+pub trait Screen: ScreenClone {
+    // every pixel goes dark
+    fn clear(&mut self);
+
+    // XORs on into the pixel at (x, y) like a single sprite bit of DRW,
+    // returns true when a lit pixel was turned off
+    fn draw_pixel(&mut self, x: usize, y: usize, on: bool) -> bool;
+}
+
+// lets VM keep deriving Clone while it holds a boxed screen
+pub trait ScreenClone {
+    fn clone_box(&self) -> Box<dyn Screen>;
+}
+
+impl<T: 'static + Screen + Clone> ScreenClone for T {
+    fn clone_box(&self) -> Box<dyn Screen> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Screen> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// the screen a VM starts with, shows nothing; VM::frame_buffer already holds the picture
+#[derive(Clone, Default)]
+pub struct NullScreen;
+
+impl Screen for NullScreen {
+    fn clear(&mut self) {}
+
+    fn draw_pixel(&mut self, _x: usize, _y: usize, _on: bool) -> bool {
+        false
+    }
+}
+
+// a screen that keeps its own copy of the pixels, e.g. for checking what a backend was sent,
+// large enough for the SUPER-CHIP 128x64 mode
+#[derive(Clone)]
+pub struct MemoryScreen {
+    pixels: Vec<Vec<bool>>,
+}
+
+impl MemoryScreen {
+    const WIDTH: usize = 128;
+    const HEIGHT: usize = 64;
+
+    // pixels outside the screen are dark
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels
+            .get(y)
+            .and_then(|row| row.get(x))
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+impl Default for MemoryScreen {
+    fn default() -> Self {
+        MemoryScreen {
+            pixels: vec![vec![false; MemoryScreen::WIDTH]; MemoryScreen::HEIGHT],
+        }
+    }
+}
+
+impl Screen for MemoryScreen {
+    fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|row| row.fill(false));
+    }
+
+    fn draw_pixel(&mut self, x: usize, y: usize, on: bool) -> bool {
+        match self.pixels.get_mut(y).and_then(|row| row.get_mut(x)) {
+            Some(pixel) => {
+                let erased = *pixel && on;
+                *pixel ^= on;
+                erased
+            }
+            None => false,
+        }
+    }
+}
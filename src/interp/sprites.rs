@@ -1,4 +1,4 @@
-struct Sprite([u8; 5]);
+pub struct Sprite(pub [u8; 5]);
 
 const SPRITE_0: Sprite = Sprite([0xF0, 0x90, 0x90, 0x90, 0xF0]);
 const SPRITE_1: Sprite = Sprite([0x20, 0x60, 0x20, 0x20, 0x70]);
@@ -16,3 +16,47 @@ const SPRITE_C: Sprite = Sprite([0xF0, 0x80, 0x80, 0x80, 0xF0]);
 const SPRITE_D: Sprite = Sprite([0xE0, 0x90, 0x90, 0x90, 0xE0]);
 const SPRITE_E: Sprite = Sprite([0xF0, 0x80, 0xF0, 0x80, 0xF0]);
 const SPRITE_F: Sprite = Sprite([0xF0, 0x80, 0xF0, 0x80, 0x80]);
+
+// the hexadecimal digits 0 through F in order, copied into the interpreter area on reset
+pub const FONT: [Sprite; 16] = [
+    SPRITE_0, SPRITE_1, SPRITE_2, SPRITE_3, SPRITE_4, SPRITE_5, SPRITE_6, SPRITE_7, SPRITE_8,
+    SPRITE_9, SPRITE_A, SPRITE_B, SPRITE_C, SPRITE_D, SPRITE_E, SPRITE_F,
+];
+
+// SUPER-CHIP 8x10 digits, only 0 through 9 exist
+pub struct LargeSprite(pub [u8; 10]);
+
+const LARGE_SPRITE_0: LargeSprite =
+    LargeSprite([0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C]);
+const LARGE_SPRITE_1: LargeSprite =
+    LargeSprite([0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C]);
+const LARGE_SPRITE_2: LargeSprite =
+    LargeSprite([0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF]);
+const LARGE_SPRITE_3: LargeSprite =
+    LargeSprite([0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C]);
+const LARGE_SPRITE_4: LargeSprite =
+    LargeSprite([0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06]);
+const LARGE_SPRITE_5: LargeSprite =
+    LargeSprite([0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C]);
+const LARGE_SPRITE_6: LargeSprite =
+    LargeSprite([0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C]);
+const LARGE_SPRITE_7: LargeSprite =
+    LargeSprite([0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60]);
+const LARGE_SPRITE_8: LargeSprite =
+    LargeSprite([0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C]);
+const LARGE_SPRITE_9: LargeSprite =
+    LargeSprite([0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C]);
+
+// copied into the interpreter area right after FONT
+pub const LARGE_FONT: [LargeSprite; 10] = [
+    LARGE_SPRITE_0,
+    LARGE_SPRITE_1,
+    LARGE_SPRITE_2,
+    LARGE_SPRITE_3,
+    LARGE_SPRITE_4,
+    LARGE_SPRITE_5,
+    LARGE_SPRITE_6,
+    LARGE_SPRITE_7,
+    LARGE_SPRITE_8,
+    LARGE_SPRITE_9,
+];
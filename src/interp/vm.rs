@@ -1,11 +1,36 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use anyhow::Ok;
-use random::Source;
 
-use super::opcode::{Addr, Byte, OpCode, V};
+use super::buzzer::{Buzzer, SilentBuzzer};
+use super::clock::{Clock, TIMER_HZ};
+use super::error::{AssembleError, DecodeError, VmError};
+use super::keypad::{Keypad, MemoryKeypad};
+use super::opcode::{Addr, Byte, OpCategory, OpCode, V};
+use super::rng::Chip8Rng;
+use super::screen::{NullScreen, Screen};
+use super::sprites::{FONT, LARGE_FONT};
 
 const MEMORY_BYTES: usize = 4096;
 const REGISTER_COUNT: usize = 16;
 const STACK_LENGTH: usize = 16;
+const DISPLAY_WIDTH: usize = 64;
+const DISPLAY_HEIGHT: usize = 32;
+const HIRES_DISPLAY_WIDTH: usize = 128; // SUPER-CHIP high resolution mode
+const HIRES_DISPLAY_HEIGHT: usize = 64;
+const PROGRAM_START: usize = 0x200;
+const FONT_BASE: usize = 0x050; // conventional location of the hex font inside 0x000 - 0x1FF, see set_font_base
+const FONT_SPRITE_BYTES: usize = 5;
+const LARGE_FONT_SPRITE_BYTES: usize = 10; // SUPER-CHIP digits, right after the 16 small ones
+const FONT_AREA_BYTES: usize =
+    FONT.len() * FONT_SPRITE_BYTES + LARGE_FONT.len() * LARGE_FONT_SPRITE_BYTES;
+const KEY_COUNT: usize = 16;
+const DEFAULT_TRACE_CAPACITY: usize = 256;
+const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 600;
+#[cfg(feature = "serde")]
+const SAVE_STATE_MAGIC: &[u8] = b"CH8S";
 
 #[derive(Clone)]
 pub struct VM {
@@ -66,23 +91,286 @@ pub struct VM {
     */
     stack: [u16; STACK_LENGTH],
 
+    /* The original implementation of the Chip-8 language used a 64x32-pixel monochrome display with this format:
+    +------------------+
+    |(0,0)       (63,0)|
+    |(0,31)     (63,31)|
+    +------------------+
+    indexed as display[y][x], true means the pixel is on.
+    SUPER-CHIP adds a 128x64 high resolution mode, the buffer is reallocated to the active size.
+    */
+    display: Vec<Vec<bool>>,
+    hires: bool,
+
     // screen, random device and so on
     pheriphal: Pheriphal,
+
+    // behaviours that differ between CHIP-8 interpreters
+    quirks: Quirks,
+
+    // set when the last tick was a jump to its own address, the usual "program done" idiom
+    halted: bool,
+
+    // set by tick_timers, a DRW under the display_wait quirk waits for it and clears it,
+    // nothing else clears it so it survives an Fx0A key wait
+    vblank: bool,
+    // set when the last tick was such a DRW waiting for vblank, nothing was executed
+    stalled: bool,
+
+    // the instruction rate run() and tick_with_timers assume, see set_clock
+    clock: Clock,
+    // how far they are into the next timer step, in 1/60 of an instruction,
+    // kept across calls so short budgets still move the timers
+    timer_phase: u32,
+
+    // the last program given to load_rom, reloaded by cold_reset at the address it was loaded at
+    rom: Vec<u8>,
+    rom_start: u16,
+
+    // where the hex font starts, the large font follows it
+    font_base: u16,
+
+    // debugging aid: warn when an opcode reads a register nothing has written since reset
+    track_uninitialized: bool,
+    written_registers: [bool; REGISTER_COUNT],
+    warnings: Vec<String>,
+    // (address, register) pairs already warned about, so a loop warns once rather than on every pass
+    reported_reads: HashSet<(u16, u8)>,
+
+    // debugging aid: addresses run() stops at
+    breakpoints: HashSet<u16>,
+
+    // debugging aid: run() stops once this many 0000 words in a row have executed, see detect_noop_sled
+    // sled_start is where the current run of them began
+    sled_threshold: Option<usize>,
+    sled_length: usize,
+    sled_start: u16,
+
+    // debugging aid: the last trace_capacity instructions tick() executed, oldest first
+    // up to twice the capacity is kept so dropping old entries is a single drain every trace_capacity ticks
+    tracing: bool,
+    trace: Vec<TraceEntry>,
+    trace_capacity: usize,
+    // OpCategory::mask bits of the instructions that are traced
+    trace_filter: u16,
+
+    // debugging aid: undecodable words tick() stepped over and where, see collect_diagnostics
+    diagnosing: bool,
+    diagnostics: Vec<(u16, DecodeError)>,
+
+    // debugging aid: the pixels the last DRW erased, in drawing order, see record_collisions
+    recording_collisions: bool,
+    last_collision: Vec<(usize, usize)>,
+
+    // see quirk_usage
+    quirk_usage: QuirkUsageReport,
+
+    // the first key pressed in the last apply_key_events batch, held for an Fx0A even once released
+    batch_press: Option<u8>,
+
+    // set whenever the display needs presenting again, see take_dirty
+    // blank_presented: a CLS has already marked the blank display dirty and nothing was drawn since
+    dirty: bool,
+    blank_presented: bool,
+
+    // phosphor persistence for frontends that emulate ghosting, see set_persistence
+    // one brightness per pixel row by row, display stays what collisions and frame_buffer() go by
+    persistence: bool,
+    intensity: Vec<u8>,
 }
 
 #[derive(Clone)]
 struct Pheriphal {
-    random_device: Box<random::Xorshift128Plus>,
+    random_device: Box<dyn Chip8Rng>,
+
+    // mirrors every pixel change of display, see set_screen
+    screen: Box<dyn Screen>,
+
+    // the 16-key hexadecimal keypad, see set_keypad
+    keypad: Box<dyn Keypad>,
+
+    // follows the sound timer, see set_buzzer
+    buzzer: Box<dyn Buzzer>,
+
+    // frontend observers, see set_draw_callback and set_sound_callback
+    callbacks: Callbacks,
+}
+
+pub type DrawCallback = Box<dyn FnMut(&[Vec<bool>])>;
+pub type SoundCallback = Box<dyn FnMut(bool)>;
+
+#[derive(Default)]
+struct Callbacks {
+    draw: Option<DrawCallback>,
+    sound: Option<SoundCallback>,
+}
+
+// closures cannot be cloned, and they belong to the frontend that registered them anyway,
+// so a cloned VM starts without callbacks
+impl Clone for Callbacks {
+    fn clone(&self) -> Self {
+        Callbacks::default()
+    }
+}
+
+// byte order of the 16-bit words in a ROM image, Chip-8 itself is big-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+// switches between the behaviours of historical interpreters, where ROMs disagree on what an opcode does
+// the defaults keep the behaviour this VM has always had, except that memory_wrap is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    // COSMAC VIP: 8xy6 / 8xyE shift Vy and store the result in Vx
+    // CHIP-48 / SUPER-CHIP: they shift Vx in place and ignore Vy
+    // with y == x, as in 8336, both shift Vx in place, and the encoder keeps y == x
+    pub shift_uses_vy: bool,
+    // where Fx55 / Fx65 leave I, see MemoryIndex
+    pub memory_index: MemoryIndex,
+    // Amiga CHIP-8: Fx1E sets VF to 1 when I goes past 0xFFF and to 0 otherwise
+    // everything else: VF is left alone
+    pub add_i_sets_vf: bool,
+    // COSMAC VIP: Dxyn waits for the vertical blank interrupt, so at most one sprite is drawn per 60Hz frame
+    // everything else: Dxyn runs right away
+    // tick() stalls on a DRW until the next tick_timers, so the frontend has to call tick_timers
+    // once per frame between batches of ticks, as step_frame does, or the program never gets past it
+    // an Fx0A key wait takes priority: a vertical blank passing while it waits is kept, so a DRW right
+    // after it runs on the frame the key arrives, and a key pressed while a DRW stalls is still held for Fx0A
+    pub display_wait: bool,
+    // 4K machines: DRW, Fx33, Fx55 and Fx65 with I past 0xFFF wrap around to the start of memory
+    // off: such an access stops the program with VmError::MemoryAccess
+    pub memory_wrap: bool,
+    // SUPER-CHIP: Dxyn clips at the screen edges, only the origin wraps, and VF is the number of sprite rows
+    // that erased a pixel plus the number of rows cut off at the bottom; clipped rows never collide
+    // everything else: the sprite wraps around and VF is 1 when any pixel was erased
+    pub clip_sprites_with_counting: bool,
+    // some SUPER-CHIP ROMs in the 64x32 mode: the Dxyn origin wraps at another (x, y) modulus, e.g. (128, 64)
+    // None: it wraps at the size of the screen; pixels past the screen edge then wrap or clip as usual
+    pub draw_origin_wrap: Option<(usize, usize)>,
+    // debugging aid: a Dxyn with I in 0x000 - 0x1FF, other than at a font digit, stops with VmError::DrawFromReserved
+    // off: the bytes there are drawn like any other sprite
+    pub strict_draw_source: bool,
+    // debugging aid: Fx33, Fx55 and poke writing to 0x000 - 0x1FF stop with VmError::WriteToReserved
+    // off: the interpreter area is ordinary RAM
+    pub protect_interpreter_region: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            memory_index: MemoryIndex::default(),
+            add_i_sets_vf: false,
+            display_wait: false,
+            memory_wrap: true,
+            clip_sprites_with_counting: false,
+            draw_origin_wrap: None,
+            strict_draw_source: false,
+            protect_interpreter_region: false,
+        }
+    }
+}
+
+// what Fx55 / Fx65 do to I after copying V0 through Vx
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryIndex {
+    // CHIP-48 / SUPER-CHIP 1.1: I is left unchanged
+    #[default]
+    Unchanged,
+    // SUPER-CHIP 1.0: I = I + x
+    IncrementByX,
+    // COSMAC VIP: I points past the last register stored or loaded, I = I + x + 1
+    IncrementPastX,
+}
+
+// why run(), step_over() or step_out() returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    // the tick budget ran out
+    Budget,
+    // the program parked itself in a jump to its own address
+    Halted,
+    // pc reached a breakpoint, the instruction at that address has not executed yet
+    Breakpoint(u16),
+    // step_over or step_out got where it was going
+    Stepped,
+    // a run of 0000 words starting here executed, pc most likely ran off into zeroed RAM
+    RanOffTheRails(u16),
+}
+
+// which quirk-sensitive instructions a program executed since the last reset, to help pick the Quirks for a ROM
+// a field that stays false is a quirk the program runs the same under either setting
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuirkUsageReport {
+    // 8xy6 / 8xyE with y != x, see Quirks::shift_uses_vy
+    pub shift_source: bool,
+    // Fx55 / Fx65, see Quirks::memory_index
+    pub memory_index: bool,
+    // Fx1E, see Quirks::add_i_sets_vf
+    pub add_i_vf: bool,
+    // Dxyn, see Quirks::display_wait
+    pub display_wait: bool,
+    // Dxyn with a sprite crossing the right or bottom edge, see Quirks::clip_sprites_with_counting
+    pub clipping: bool,
+}
+
+// the machine state right before tick() executed an instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode_word: u16,
+    pub mnemonic: String,
+    pub registers_before: [u8; REGISTER_COUNT],
+}
+
+// what run() did: ticks that executed an instruction and ticks a DRW spent waiting under display_wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunReport {
+    pub executed: usize,
+    pub stalled: usize,
+    pub stopped: Stopped,
+}
+
+// what a single instruction read and wrote, for cross-checking against conformance test vectors
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SideEffects {
+    pub registers_read: Vec<u8>,
+    pub registers_written: Vec<u8>,
+    pub i_written: bool,
+    pub memory_read: Vec<u16>,
+    pub memory_written: Vec<u16>,
+    pub pc_delta: i32,
 }
 
 impl VM {
+    // seeded from the clock, so every run plays differently; use new_with_seed for repeatable runs
     pub fn new() -> VM {
-        let device = random::default(42);
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(42);
+        VM::new_with_seed(seed)
+    }
+
+    pub fn new_with_seed(seed: u64) -> VM {
+        VM::with_rng(Box::new(random::default(seed)))
+    }
+
+    pub fn with_rng(rng: Box<dyn Chip8Rng>) -> VM {
         let p = Pheriphal {
-            random_device: Box::new(device),
+            random_device: rng,
+            screen: Box::new(NullScreen),
+            keypad: Box::new(MemoryKeypad::default()),
+            buzzer: Box::new(SilentBuzzer),
+            callbacks: Callbacks::default(),
         };
 
-        VM {
+        let mut vm = VM {
             memory: [0; MEMORY_BYTES],
             registers: [0; REGISTER_COUNT],
             i: 0,
@@ -91,11 +379,158 @@ impl VM {
             pc: 0,
             sp: 0,
             stack: [0; STACK_LENGTH],
+            display: vec![vec![false; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+            hires: false,
             pheriphal: p,
+            quirks: Quirks::default(),
+            halted: false,
+            vblank: false,
+            stalled: false,
+            clock: Clock::new(DEFAULT_INSTRUCTIONS_PER_SECOND),
+            timer_phase: 0,
+            rom: Vec::new(),
+            rom_start: PROGRAM_START as u16,
+            font_base: FONT_BASE as u16,
+            track_uninitialized: false,
+            written_registers: [false; REGISTER_COUNT],
+            warnings: Vec::new(),
+            reported_reads: HashSet::new(),
+            breakpoints: HashSet::new(),
+            sled_threshold: None,
+            sled_length: 0,
+            sled_start: 0,
+            tracing: false,
+            trace: Vec::new(),
+            trace_capacity: DEFAULT_TRACE_CAPACITY,
+            trace_filter: OpCategory::ALL,
+            diagnosing: false,
+            diagnostics: Vec::new(),
+            recording_collisions: false,
+            last_collision: Vec::new(),
+            quirk_usage: QuirkUsageReport::default(),
+            batch_press: None,
+            dirty: false,
+            blank_presented: false,
+            persistence: false,
+            intensity: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+        };
+        vm.load_font();
+        vm
+    }
+
+    // the common case in one call: a default VM with the font loaded and rom at 0x200, pc pointing at it
+    pub fn with_rom(rom: &[u8]) -> anyhow::Result<VM> {
+        let mut vm = VM::new();
+        vm.load_rom(rom)?;
+        Ok(vm)
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> VM {
+        let mut vm = VM::new();
+        vm.quirks = quirks;
+        vm
+    }
+
+    fn load_font(&mut self) {
+        for (digit, sprite) in FONT.iter().enumerate() {
+            let start = self.font_base as usize + digit * FONT_SPRITE_BYTES;
+            self.memory[start..start + FONT_SPRITE_BYTES].copy_from_slice(&sprite.0);
+        }
+        for (digit, sprite) in LARGE_FONT.iter().enumerate() {
+            let start = self.large_font_base() + digit * LARGE_FONT_SPRITE_BYTES;
+            self.memory[start..start + LARGE_FONT_SPRITE_BYTES].copy_from_slice(&sprite.0);
+        }
+    }
+
+    fn large_font_base(&self) -> usize {
+        self.font_base as usize + FONT.len() * FONT_SPRITE_BYTES
+    }
+
+    // moves the fonts, e.g. to 0x000 as some interpreters have them; both must fit below 0x200
+    // the old location is zeroed, Fx29 and Fx30 point into the new one
+    pub fn set_font_base(&mut self, base: u16) -> anyhow::Result<()> {
+        if base as usize + FONT_AREA_BYTES > PROGRAM_START {
+            return Err(VmError::InvalidFontBase(base).into());
+        }
+        let old = self.font_base as usize;
+        self.memory[old..old + FONT_AREA_BYTES].fill(0);
+        self.font_base = base;
+        self.load_font();
+        Ok(())
+    }
+
+    // copies the program to 0x200 and points pc at it, a copy is kept so cold_reset can reload it
+    pub fn load_rom(&mut self, bytes: &[u8]) -> anyhow::Result<()> {
+        self.load_rom_at(bytes, PROGRAM_START as u16)
+    }
+
+    // like load_rom for programs with another entry point, e.g. 0x600 for ETI 660 programs
+    // start must lie in program space, 0x200 to 0xFFF
+    pub fn load_rom_at(&mut self, bytes: &[u8], start: u16) -> anyhow::Result<()> {
+        let begin = start as usize;
+        if !(PROGRAM_START..MEMORY_BYTES).contains(&begin) {
+            return Err(VmError::InvalidProgramStart(start).into());
+        }
+        if bytes.len() > MEMORY_BYTES - begin {
+            return Err(VmError::RomTooLarge(bytes.len()).into());
+        }
+        self.memory[begin..begin + bytes.len()].copy_from_slice(bytes);
+        self.pc = start;
+        self.halted = false;
+        self.rom = bytes.to_vec();
+        self.rom_start = start;
+        Ok(())
+    }
+
+    pub fn load_rom_file<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let bytes = std::fs::read(path)?;
+        self.load_rom(&bytes)
+    }
+
+    // loads a ROM whose words may be stored little-endian by some export tools,
+    // each word is swapped back to big-endian before it lands at 0x200; a trailing odd byte is kept as is
+    pub fn load_rom_endian(&mut self, bytes: &[u8], endian: Endian) -> anyhow::Result<()> {
+        match endian {
+            Endian::Big => self.load_rom(bytes),
+            Endian::Little => {
+                let mut swapped = bytes.to_vec();
+                for word in swapped.chunks_exact_mut(2) {
+                    word.swap(0, 1);
+                }
+                self.load_rom(&swapped)
+            }
+        }
+    }
+
+    // swaps the random source mid-run, registers, memory and pc are left as they are
+    pub fn replace_rng(&mut self, rng: Box<dyn Chip8Rng>) {
+        self.pheriphal.random_device = rng;
+    }
+
+    // routes pixels to a frontend backend instead of the default NullScreen,
+    // the new screen is cleared and brought up to date with the current display
+    pub fn set_screen(&mut self, screen: Box<dyn Screen>) {
+        self.pheriphal.screen = screen;
+        self.sync_screen();
+    }
+
+    fn sync_screen(&mut self) {
+        let screen = &mut self.pheriphal.screen;
+        screen.clear();
+        for (y, row) in self.display.iter().enumerate() {
+            for (x, &lit) in row.iter().enumerate() {
+                if lit {
+                    screen.draw_pixel(x, y, true);
+                }
+            }
         }
     }
 
     pub fn execute(&mut self, op: OpCode) -> anyhow::Result<()> {
+        if self.track_uninitialized {
+            // pc is already past the instruction, see tick
+            self.check_uninitialized_reads(&op, self.pc.wrapping_sub(2));
+        }
         match op {
             OpCode::System(nnn) => self.system(nnn),
             OpCode::ClearScreen => self.clearscreen(),
@@ -132,369 +567,3670 @@ impl VM {
             OpCode::LoadBCD(x) => self.load_bcd(x),
             OpCode::SaveRegisters(x) => self.save_registers(x),
             OpCode::LoadRegisters(x) => self.load_registers(x),
+            OpCode::ScrollDown(nibble) => self.scroll_down(nibble),
+            OpCode::ScrollRight => self.scroll_right(),
+            OpCode::ScrollLeft => self.scroll_left(),
+            OpCode::LowRes => self.low_res(),
+            OpCode::HighRes => self.high_res(),
+            OpCode::LoadLargeSprite(x) => self.load_large_sprite(x),
+            OpCode::ScrollUp(nibble) => self.scroll_up(nibble),
         }
     }
-}
 
-// implementation for opcodes
-impl VM {
-    fn system(&mut self, nnn: Addr) -> anyhow::Result<()> {
-        /* 0nnn - SYS addr
-        Jump to a machine code routine at nnn.
-        This instruction is only used on the old computers on which Chip-8 was originally implemented.
-        It is ignored by modern interpreters.
-        */
-        Ok(())
+    // one fetch-decode-execute cycle: pc is moved past the instruction before it runs,
+    // so jumps, calls and skips all work relative to the next instruction
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        let op = match self.instruction_at(self.pc) {
+            Result::Ok(op) => op,
+            // in diagnostic mode a word that is not an instruction is noted and stepped over
+            Err(e) if self.diagnosing && !matches!(e, DecodeError::AddressOutOfRange(_)) => {
+                self.diagnostics.push((self.pc, e));
+                self.halted = false;
+                self.stalled = false;
+                self.sled_length = 0;
+                self.pc += 2;
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.halted = matches!(op, OpCode::Jump(Addr(target)) if target == self.pc);
+        // with display_wait a DRW leaves pc where it is until tick_timers marks the next vertical blank
+        self.stalled = self.quirks.display_wait && matches!(op, OpCode::Draw(..)) && !self.vblank;
+        if self.stalled {
+            return Ok(());
+        }
+        if matches!(op, OpCode::Draw(..)) {
+            self.vblank = false;
+        }
+        if self.tracing && self.trace_filter & op.category().mask() != 0 {
+            self.record_trace(&op);
+        }
+        if op == OpCode::System(Addr(0)) {
+            if self.sled_length == 0 {
+                self.sled_start = self.pc;
+            }
+            self.sled_length += 1;
+        } else {
+            self.sled_length = 0;
+        }
+        self.pc += 2;
+        self.execute(op)
     }
 
-    fn clearscreen(&mut self) -> anyhow::Result<()> {
-        /* 00E0 - CLS
-        Clear the display.
-        */
-        todo!()
+    // true once the program has parked itself in a jump to its own address,
+    // a frontend can stop ticking as nothing but the timers will change from here on
+    pub fn is_halted(&self) -> bool {
+        self.halted
     }
 
-    fn execute_return(&mut self) -> anyhow::Result<()> {
-        /* 00EE - RET
-        Return from a subroutine.
-        The interpreter sets the program counter to the address at the top of the stack,
-        then subtracts 1 from the stack pointer.
-        */
-        self.pc = self.stack[self.sp as usize];
-        self.sp -= 1;
-        Ok(())
+    // true when the last tick was a DRW waiting for the next tick_timers under the display_wait quirk,
+    // pc did not move and nothing was executed
+    pub fn is_stalled(&self) -> bool {
+        self.stalled
     }
 
-    fn jump(&mut self, nnn: Addr) -> anyhow::Result<()> {
-        /* 1nnn - JP addr
-        Jump to location nnn.
-        The interpreter sets the program counter to nnn.
-        */
-        self.pc = nnn.0;
+    // one 60Hz frame: ipf instructions followed by a single timer step, see Clock for choosing ipf
+    pub fn step_frame(&mut self, ipf: usize) -> anyhow::Result<()> {
+        for _ in 0..ipf {
+            self.tick()?;
+        }
+        self.tick_timers();
         Ok(())
     }
 
-    fn execute_call(&mut self, nnn: Addr) -> anyhow::Result<()> {
-        /* 2nnn - CALL addr
-        Call subroutine at nnn.
-        The interpreter increments the stack pointer, then puts the current PC on the top of the stack.
-        The PC is then set to nnn.
-        */
-        self.sp += 1;
-        self.stack[self.sp as usize] = self.pc;
-        self.pc = nnn.0;
+    // step_frame for latency sensitive audio: on_sound gets true on the very tick the sound timer
+    // is set from zero and false on the tick it runs out, so a beep shorter than a frame is not lost
+    pub fn run_frame_with_callbacks(
+        &mut self,
+        cycles: usize,
+        mut on_sound: impl FnMut(bool),
+    ) -> anyhow::Result<()> {
+        let mut sounding = self.sound_active();
+        let mut check = |vm: &VM| {
+            if vm.sound_active() != sounding {
+                sounding = vm.sound_active();
+                on_sound(sounding);
+            }
+        };
+        for _ in 0..cycles {
+            self.tick()?;
+            check(self);
+        }
+        self.tick_timers();
+        check(self);
         Ok(())
     }
 
-    fn skip_equal(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
-        /* 3xkk - SE Vx, byte
-        Skip next instruction if Vx = kk.
-        The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
-        */
-        if self.registers[x.0 as usize] == kk.0 {
-            self.pc += 2;
+    // one tick plus its share of a timer step, for hosts that simply call this in a loop at the clock rate:
+    // each tick is worth 60 / instructions_per_second of a step and the remainder carries over,
+    // so the timers step exactly 60 times per second's worth of ticks
+    // the price is accuracy: a step lands on an instruction boundary, up to one instruction late,
+    // and the timers only keep 60Hz in wall time if the host really ticks at the clock rate;
+    // step_frame driven by a 60Hz frame clock keeps them on time however the instructions are paced
+    pub fn tick_with_timers(&mut self) -> anyhow::Result<()> {
+        self.tick()?;
+        let rate = self.clock.instructions_per_second().max(1);
+        self.timer_phase += TIMER_HZ;
+        if self.timer_phase >= rate {
+            self.timer_phase -= rate;
+            self.tick_timers();
         }
         Ok(())
     }
 
-    fn skip_not_equal(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
-        /* 4xkk - SNE Vx, byte
-        Skip next instruction if Vx != kk.
-        The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
-        */
-        if self.registers[x.0 as usize] != kk.0 {
-            self.pc += 2;
-        }
-        Ok(())
+    // the instruction rate run() and tick_with_timers pace the timers by, 600 per second unless set,
+    // i.e. a timer step every 10 instructions
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+        self.timer_phase = 0;
     }
 
-    fn skip_equal_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 5xy0 - SE Vx, Vy
-        Skip next instruction if Vx = Vy.
-        The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
-        */
-        if self.registers[x.0 as usize] == self.registers[y.0 as usize] {
-            self.pc += 2;
-        }
-        Ok(())
+    pub fn clock(&self) -> Clock {
+        self.clock
     }
 
-    // todo 似乎不叫 load，应该叫 set_literal?
-    fn load(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
-        /* 6xkk - LD Vx, byte
-        Set Vx = kk.
-        The interpreter puts the value kk into register Vx.
-        */
-        self.registers[x.0 as usize] = kk.0;
-        Ok(())
+    // headless execution of up to max_ticks ticks with the timers paced by the clock as in tick_with_timers,
+    // returns how many executed an instruction,
+    // how many stalled on a DRW under display_wait and why it stopped:
+    // the budget ran out, the program parked itself with a jump to its own address,
+    // pc reached a breakpoint, or it ran into zeroed RAM, see detect_noop_sled.
+    // A breakpoint stops before its instruction executes, and the first tick of each run
+    // is not checked, so calling run again continues past it;
+    // a DRW stalled on a breakpoint is not checked again until it has executed
+    pub fn run(&mut self, max_ticks: usize) -> anyhow::Result<RunReport> {
+        let mut report = RunReport {
+            executed: 0,
+            stalled: 0,
+            stopped: Stopped::Budget,
+        };
+        for n in 0..max_ticks {
+            if n > 0 && !self.is_stalled() && self.breakpoints.contains(&self.pc) {
+                report.stopped = Stopped::Breakpoint(self.pc);
+                return Ok(report);
+            }
+            self.tick_with_timers()?;
+            if self.is_stalled() {
+                report.stalled += 1;
+            } else {
+                report.executed += 1;
+            }
+            if self.is_halted() {
+                report.stopped = Stopped::Halted;
+                return Ok(report);
+            }
+            if self.ran_off_the_rails() {
+                report.stopped = Stopped::RanOffTheRails(self.sled_start);
+                return Ok(report);
+            }
+        }
+        Ok(report)
     }
 
-    fn add(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
-        /* 7xkk - ADD Vx, byte
-        Set Vx = Vx + kk.
-        Adds the value kk to the value of register Vx, then stores the result in Vx.
-        */
-        self.registers[x.0 as usize] += kk.0;
-        Ok(())
+    // decode the instruction stored at an arbitrary address, pc is left untouched
+    pub fn instruction_at(&self, addr: u16) -> Result<OpCode, DecodeError> {
+        let start = addr as usize;
+        if start + 1 >= MEMORY_BYTES {
+            return Err(DecodeError::AddressOutOfRange(addr));
+        }
+        OpCode::try_from(u16::from_be_bytes([
+            self.memory[start],
+            self.memory[start + 1],
+        ]))
     }
 
-    // todo 似乎也应该叫 set，copy 之类的？
-    fn load_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy0 - LD Vx, Vy
-        Set Vx = Vy.
-        Stores the value of register Vy in register Vx.
-        */
-        self.registers[x.0 as usize] = self.registers[y.0 as usize];
-        Ok(())
+    // the instruction about to run and its mnemonic, for debuggers; pc is not advanced
+    pub fn peek(&self) -> anyhow::Result<(OpCode, String)> {
+        let op = self.instruction_at(self.pc)?;
+        Ok((op.clone(), op.into()))
     }
 
-    fn or_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy1 - OR Vx, Vy
-        Set Vx = Vx OR Vy.
-        Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
-        A bitwise OR compares the corrseponding bits from two values, and if either bit is 1,
-        then the same bit in the result is also 1. Otherwise, it is 0.
-        */
+    // human readable instruction at pc for a status bar, e.g. "0x0200: DRW V0, V1, 5"
+    pub fn current_opcode_string(&self) -> String {
+        match self.instruction_at(self.pc) {
+            Result::Ok(op) => {
+                let mnemonic: String = op.into();
+                format!("{:#06x}: {}", self.pc, mnemonic)
+            }
+            Err(e) => format!("{:#06x}: cannot decode ({})", self.pc, e),
+        }
+    }
 
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] | self.registers[y.0 as usize];
-        Ok(())
+    // display_height() rows of display_width() pixels
+    pub fn frame_buffer(&self) -> &[Vec<bool>] {
+        &self.display
     }
 
-    fn and_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy2 - AND Vx, Vy
-        Set Vx = Vx AND Vy.
-        Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
-        A bitwise AND compares the corrseponding bits from two values, and if both bits are 1,
-         then the same bit in the result is also 1. Otherwise, it is 0.
-        */
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] & self.registers[y.0 as usize];
-        Ok(())
+    // the lit pixels as (x, y), row by row from the top left
+    pub fn set_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.display.iter().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|&(_, &lit)| lit)
+                .map(move |(x, _)| (x, y))
+        })
     }
 
-    fn xor_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy3 - XOR Vx, Vy
-        Set Vx = Vx XOR Vy.
-        Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx. An exclusive OR compares the corrseponding bits from two values, and if the bits are not both the same, then the corresponding bit in the result is set to 1. Otherwise, it is 0.
-        */
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] ^ self.registers[y.0 as usize];
-        Ok(())
+    // pixels numbered row by row, i = y * display_width() + x; past the last pixel is dark
+    pub fn is_pixel_set_at_index(&self, i: usize) -> bool {
+        let width = self.display_width();
+        self.display
+            .get(i / width)
+            .is_some_and(|row| row[i % width])
     }
 
-    fn add_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy4 - ADD Vx, Vy
-        Set Vx = Vx + Vy, set VF = carry.
-        The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
-        */
-        let r = self.registers[x.0 as usize] as u16 + self.registers[y.0 as usize] as u16;
-        if r > u8::MAX as u16 {
-            self.registers[0x0f] = 1;
+    pub fn display_width(&self) -> usize {
+        if self.hires {
+            HIRES_DISPLAY_WIDTH
         } else {
-            self.registers[0x0f] = 0;
+            DISPLAY_WIDTH
         }
-        self.registers[x.0 as usize] = r as u8; // todo verify
-        Ok(())
     }
 
-    fn sub_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy5 - SUB Vx, Vy
-        Set Vx = Vx - Vy, set VF = NOT borrow.
-        If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
-        */
-        if self.registers[x.0 as usize] > self.registers[y.0 as usize] {
-            self.registers[0x0f] = 1;
+    pub fn display_height(&self) -> usize {
+        if self.hires {
+            HIRES_DISPLAY_HEIGHT
         } else {
-            self.registers[0x0f] = 0;
+            DISPLAY_HEIGHT
         }
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] - self.registers[y.0 as usize];
-        Ok(())
     }
 
-    fn shr_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy6 - SHR Vx {, Vy}
-        Set Vx = Vx SHR 1.
-        If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
-        */
-        if self.registers[x.0 as usize] & 0b0000_0001 == 1 {
-            self.registers[0x0f] = 1;
-        } else {
-            self.registers[0x0f] = 0;
-        }
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] >> 1;
-        Ok(())
+    // (width, height) of the active mode, all drawing and rendering goes by this
+    pub fn screen_dimensions(&self) -> (usize, usize) {
+        (self.display_width(), self.display_height())
     }
 
-    fn sub_not_borrow_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xy7 - SUBN Vx, Vy
-        Set Vx = Vy - Vx, set VF = NOT borrow.
-        If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
-        */
-        if self.registers[y.0 as usize] > self.registers[x.0 as usize] {
-            self.registers[0x0f] = 1;
-        } else {
-            self.registers[0x0f] = 0;
+    // switches between 64x32 and the SUPER-CHIP 128x64 mode, the display is blank afterwards
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear_display();
+    }
+
+    fn clear_display(&mut self) {
+        let (width, height) = self.screen_dimensions();
+        self.display = vec![vec![false; width]; height];
+        self.pheriphal.screen.clear();
+        // a cleared pixel keeps glowing, only a resolution switch starts the intensities over
+        if self.intensity.len() != width * height {
+            self.intensity = vec![0; width * height];
         }
-        self.registers[x.0 as usize] = self.registers[y.0 as usize] - self.registers[x.0 as usize];
-        Ok(())
     }
 
-    fn shl_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 8xyE - SHL Vx {, Vy}
-        Set Vx = Vx SHL 1.
-        If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
-        */
-        if self.registers[x.0 as usize] & 0b1000_0000 == 1 {
-            self.registers[0x0f] = 1;
+    // with persistence on a pixel that goes dark fades out over the next few tick_timers calls
+    // instead of going dark at once, lit pixels are always at full brightness
+    pub fn set_persistence(&mut self, enabled: bool) {
+        self.persistence = enabled;
+        self.intensity.fill(0);
+        self.feed_intensity();
+    }
+
+    // brightness of the pixel at (x, y), 255 for a lit pixel and 0 for a dark one without persistence
+    // pixels outside the screen are dark
+    pub fn intensity(&self, x: usize, y: usize) -> u8 {
+        let (width, height) = self.screen_dimensions();
+        if x >= width || y >= height {
+            return 0;
+        }
+        if self.display[y][x] {
+            u8::MAX
+        } else if self.persistence {
+            self.intensity[y * width + x]
         } else {
-            self.registers[0x0f] = 0;
+            0
         }
-        self.registers[x.0 as usize] = self.registers[x.0 as usize] << 1;
-        Ok(())
     }
 
-    fn skip_not_equal_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
-        /* 9xy0 - SNE Vx, Vy
-        Skip next instruction if Vx != Vy.
-        The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
-        */
-        if self.registers[x.0 as usize] != self.registers[y.0 as usize] {
-            self.pc += 2;
+    // lit pixels go to full brightness, CLS only darkens pixels so after it they are left to fade
+    fn feed_intensity(&mut self) {
+        if !self.persistence {
+            return;
+        }
+        for (glow, &lit) in self.intensity.iter_mut().zip(self.display.iter().flatten()) {
+            if lit {
+                *glow = u8::MAX;
+            }
         }
-        Ok(())
     }
 
-    fn set(&mut self, nnn: Addr) -> anyhow::Result<()> {
-        /* Annn - LD I, addr
-        Set I = nnn.
-        The value of register I is set to nnn.
-        */
-        self.i = nnn.0;
-        Ok(())
+    // dark pixels lose half their brightness every 60Hz frame, so a pixel is fully dark after 8 frames
+    fn decay_intensity(&mut self) {
+        if !self.persistence {
+            return;
+        }
+        self.feed_intensity();
+        for (glow, &lit) in self.intensity.iter_mut().zip(self.display.iter().flatten()) {
+            if !lit {
+                *glow /= 2;
+            }
+        }
     }
 
-    fn jump_v0(&mut self, nnn: Addr) -> anyhow::Result<()> {
-        /* Bnnn - JP V0, addr
-        Jump to location nnn + V0.
-        The program counter is set to nnn plus the value of V0.
-        */
-        self.pc = self.registers[0] as u16 + nnn.0;
-        Ok(())
+    // the screen as text for terminals, one line per display row ending in a newline,
+    // a full block for every lit pixel and a space for every dark one
+    pub fn render_ascii(&self) -> String {
+        let (width, height) = self.screen_dimensions();
+        let mut out = String::with_capacity((width * '█'.len_utf8() + 1) * height);
+        for row in self.display.iter() {
+            out.extend(row.iter().map(|&lit| if lit { '█' } else { ' ' }));
+            out.push('\n');
+        }
+        out
     }
 
-    fn execute_random(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
-        /* Cxkk - RND Vx, byte
-        Set Vx = random byte AND kk.
-        The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx. See instruction 8xy2 for more information on AND.
-        */
-        let r = self.pheriphal.random_device.read::<u8>();
-        self.registers[x.0 as usize] = r & kk.0;
-        Ok(())
+    // render_ascii with the machine state alongside for watching a ROM in a terminal:
+    // every screen row is followed by " | " and one sidebar entry, V0 - VF, I, PC, SP, DT and ST from the top
+    pub fn render_debug(&self) -> String {
+        let mut sidebar: Vec<String> = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(x, value)| format!("V{:X}: {:#04x}", x, value))
+            .collect();
+        sidebar.push(format!("I:  {:#05x}", self.i));
+        sidebar.push(format!("PC: {:#05x}", self.pc));
+        sidebar.push(format!("SP: {}", self.sp));
+        sidebar.push(format!("DT: {:#04x}", self.dt));
+        sidebar.push(format!("ST: {:#04x}", self.st));
+
+        let mut out = String::new();
+        for (row, line) in self.render_ascii().lines().enumerate() {
+            out.push_str(line);
+            out.push_str(" | ");
+            out.push_str(sidebar.get(row).map(String::as_str).unwrap_or(""));
+            out.push('\n');
+        }
+        out
     }
 
-    fn draw(&mut self, x: V, y: V, nibble: u8) -> anyhow::Result<()> {
-        /* Dxyn - DRW Vx, Vy, nibble
-        Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
-        The interpreter reads n bytes from memory, starting at the address stored in I. These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). Sprites are XORed onto the existing screen. If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen. See instruction 8xy3 for more information on XOR, and section 2.4, Display, for more information on the Chip-8 screen and sprites.
-        */
-        todo!()
+    // read-only views of the machine state for debuggers
+    // only the low nibble of x is used, as with the register fields of an opcode
+    pub fn register(&self, x: u8) -> u8 {
+        self.registers[(x & 0x0F) as usize]
     }
 
-    fn key(&mut self, x: V) -> anyhow::Result<()> {
-        /* Ex9E - SKP Vx
-        Skip next instruction if key with the value of Vx is pressed.
-        Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
-        */
-        todo!()
+    pub fn registers(&self) -> &[u8; REGISTER_COUNT] {
+        &self.registers
     }
 
-    fn skip_not_key(&mut self, x: V) -> anyhow::Result<()> {
-        /* ExA1 - SKNP Vx
-        Skip next instruction if key with the value of Vx is not pressed.
-        Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
-        */
-        todo!()
+    pub fn i(&self) -> u16 {
+        self.i
     }
 
-    fn load_dt(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx07 - LD Vx, DT
-        Set Vx = delay timer value.
-        The value of DT is placed into Vx.
-        */
-        self.registers[x.0 as usize] = self.dt;
-        Ok(())
+    pub fn pc(&self) -> u16 {
+        self.pc
     }
 
-    fn load_key(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx0A - LD Vx, K
-        Wait for a key press, store the value of the key in Vx.
-        All execution stops until a key is pressed, then the value of that key is stored in Vx.
-        */
-        todo!()
+    pub fn sp(&self) -> u8 {
+        self.sp
     }
 
-    fn set_dt(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx15 - LD DT, Vx
-        Set delay timer = Vx.
-        DT is set equal to the value of Vx.
-        */
-        self.dt = self.registers[x.0 as usize];
-        Ok(())
+    pub fn memory_slice(&self, start: u16, len: u16) -> anyhow::Result<&[u8]> {
+        let end = start as usize + len as usize;
+        if end > MEMORY_BYTES {
+            return Err(VmError::MemoryAccess(end - 1).into());
+        }
+        Ok(&self.memory[start as usize..end])
     }
 
-    fn set_st(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx18 - LD ST, Vx
-        Set sound timer = Vx.
-        ST is set equal to the value of Vx.
-        */
-        self.st = self.registers[x.0 as usize];
+    // host-side write of a single byte, e.g. for cheats, checked like the writes of Fx33 and Fx55
+    pub fn poke(&mut self, addr: u16, value: u8) -> anyhow::Result<()> {
+        self.write_mem(addr as usize, value)
+    }
+
+    // host-side helpers, unlike Fx15/Fx18 these take the value directly instead of a register
+    pub fn set_delay_timer_value(&mut self, v: u8) {
+        self.dt = v;
+    }
+
+    pub fn set_sound_timer_value(&mut self, v: u8) {
+        self.set_sound_timer(v);
+    }
+
+    // both timers count down towards zero at 60Hz, the frontend calls this on its own clock,
+    // independent of how fast instructions are ticked
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
+        self.decay_intensity();
+        self.dt = self.dt.saturating_sub(1);
+        self.set_sound_timer(self.st.saturating_sub(1));
+    }
+
+    // called with the new display after CLS or DRW changed at least one pixel,
+    // so a GUI can redraw on change instead of polling frame_buffer every tick
+    pub fn set_draw_callback(&mut self, f: DrawCallback) {
+        self.pheriphal.callbacks.draw = Some(f);
+    }
+
+    // called with true when the buzzer starts, i.e. the sound timer leaves zero, and false when it stops
+    pub fn set_sound_callback(&mut self, f: SoundCallback) {
+        self.pheriphal.callbacks.sound = Some(f);
+    }
+
+    // replaces the default SilentBuzzer, a buzzer installed while st is non-zero starts playing right away
+    pub fn set_buzzer(&mut self, buzzer: Box<dyn Buzzer>) {
+        self.pheriphal.buzzer = buzzer;
+        if self.sound_active() {
+            self.pheriphal.buzzer.set_playing(true);
+        }
+    }
+
+    // true when the display changed since the last call, which clears it again,
+    // for frontends that present on their own clock instead of from the draw callback
+    // every DRW, scroll or mode switch that changes a pixel marks the display dirty; the first CLS
+    // after any of them does too, even on an already blank display, so the blank frame is presented once,
+    // but further CLS before the next change leave the flag alone
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn notify_draw(&mut self) {
+        self.dirty = true;
+        self.blank_presented = false;
+        if let Some(f) = self.pheriphal.callbacks.draw.as_mut() {
+            f(&self.display);
+        }
+    }
+
+    // every write to st goes through here so the buzzer and the sound callback see each start and stop
+    fn set_sound_timer(&mut self, v: u8) {
+        let was_active = self.sound_active();
+        self.st = v;
+        if was_active != self.sound_active() {
+            self.pheriphal.buzzer.set_playing(v > 0);
+            if let Some(f) = self.pheriphal.callbacks.sound.as_mut() {
+                f(v > 0);
+            }
+        }
+    }
+
+    // the buzzer sounds for as long as the sound timer is non-zero
+    pub fn sound_active(&self) -> bool {
+        self.st > 0
+    }
+
+    // called by the frontend on key down / key up, forwarded to the keypad
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.pheriphal.keypad.set_key(key, pressed);
+    }
+
+    // which quirks made a difference to the program since the last reset
+    pub fn quirk_usage(&self) -> QuirkUsageReport {
+        self.quirk_usage
+    }
+
+    // a frame's worth of key down / key up events in the order they happened, applied before the next tick,
+    // so Ex9E and ExA1 see the state after the whole batch
+    // Fx0A sees more: the first key pressed in the batch is kept until the next batch and goes to an Fx0A
+    // that runs before then, even when the same batch released it again; without one Fx0A falls back to
+    // the held keys as usual
+    pub fn apply_key_events(&mut self, events: &[(u8, bool)]) {
+        self.batch_press = events
+            .iter()
+            .find(|&&(key, pressed)| pressed && (key as usize) < KEY_COUNT)
+            .map(|&(key, _)| key);
+        for &(key, pressed) in events {
+            self.set_key(key, pressed);
+        }
+    }
+
+    // replaces the default MemoryKeypad, e.g. with one that replays recorded input
+    pub fn set_keypad(&mut self, keypad: Box<dyn Keypad>) {
+        self.pheriphal.keypad = keypad;
+    }
+
+    // expands n sprite bytes starting at addr into pixels, most significant bit is the leftmost pixel
+    // rows past the end of memory are left out
+    pub fn sprite_rows(&self, addr: u16, n: u8) -> Vec<[bool; 8]> {
+        (0..n as usize)
+            .filter_map(|offset| self.memory.get(addr as usize + offset))
+            .map(|byte| {
+                let mut row = [false; 8];
+                for (bit, pixel) in row.iter_mut().enumerate() {
+                    *pixel = byte & (0x80 >> bit) != 0;
+                }
+                row
+            })
+            .collect()
+    }
+
+    // hexdump -C style listing, 16 bytes per line:
+    // "0200  00 E0 A2 2A 60 0C 61 08  D0 1F 70 09 A2 39 D0 1F  |...*`.a...p..9..|"
+    // the range is clipped to the end of memory
+    pub fn dump_memory_hex(&self, start: u16, len: usize) -> String {
+        let start = (start as usize).min(MEMORY_BYTES);
+        let end = start.saturating_add(len).min(MEMORY_BYTES);
+        let mut out = String::new();
+        for (line, chunk) in self.memory[start..end].chunks(16).enumerate() {
+            let mut hex = String::new();
+            for (n, byte) in chunk.iter().enumerate() {
+                if n == 8 {
+                    hex.push(' ');
+                }
+                hex.push_str(&format!("{:02X} ", byte));
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            out.push_str(&format!(
+                "{:04X}  {:<49} |{}|\n",
+                start + line * 16,
+                hex,
+                ascii
+            ));
+        }
+        out
+    }
+
+    // 32-bit FNV-1a over the whole 4K of RAM, used to check save states and loaded ROMs are intact
+    pub fn memory_checksum(&self) -> u32 {
+        fnv1a(&self.memory)
+    }
+
+    // FNV-1a like memory_checksum over the display row by row, one byte of 0 or 1 per pixel,
+    // for comparing frames against reference runs without storing them
+    pub fn framebuffer_hash(&self) -> u32 {
+        let pixels: Vec<u8> = self
+            .display
+            .iter()
+            .flatten()
+            .map(|&lit| u8::from(lit))
+            .collect();
+        fnv1a(&pixels)
+    }
+
+    // the usual conformance test in one call: a VM with a fixed seed and default quirks loads rom,
+    // runs up to cycles ticks as run() does, timers included, and returns the framebuffer_hash
+    pub fn run_headless_to_hash(rom: &[u8], cycles: usize) -> anyhow::Result<u32> {
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom(rom)?;
+        vm.run(cycles)?;
+        Ok(vm.framebuffer_hash())
+    }
+
+    // soft reset: registers, timers, the stack and the screen go back to power-on values, RAM is kept
+    pub fn warm_reset(&mut self) {
+        self.halted = false;
+        self.vblank = false;
+        self.stalled = false;
+        self.timer_phase = 0;
+        self.registers = [0; REGISTER_COUNT];
+        self.i = 0;
+        self.dt = 0;
+        self.set_sound_timer(0);
+        // a loaded program restarts from its entry point
+        self.pc = if self.rom.is_empty() {
+            0
+        } else {
+            self.rom_start
+        };
+        self.sp = 0;
+        self.stack = [0; STACK_LENGTH];
+        self.set_hires(false);
+        self.written_registers = [false; REGISTER_COUNT];
+        self.reported_reads.clear();
+        self.intensity.fill(0);
+        self.sled_length = 0;
+        self.batch_press = None;
+        self.quirk_usage = QuirkUsageReport::default();
+        self.dirty = true;
+        self.blank_presented = false;
+    }
+
+    // power-on reset: everything warm_reset clears plus the whole RAM, the font and the last ROM are loaded again
+    pub fn cold_reset(&mut self) {
+        self.warm_reset();
+        self.memory = [0; MEMORY_BYTES];
+        self.load_font();
+        let begin = self.rom_start as usize;
+        self.memory[begin..begin + self.rom.len()].copy_from_slice(&self.rom);
+    }
+}
+
+// 32-bit FNV-1a, the hash behind memory_checksum
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+// one place to configure everything the VM constructors and setters cover, e.g. a COSMAC VIP with a fixed seed,
+// the same call runs in the test builder_builds_a_cosmac_vip_with_a_fixed_seed:
+// VmBuilder::new().seed(42).quirks(Quirks { shift_uses_vy: true, memory_index: MemoryIndex::IncrementPastX, ..Quirks::default() }).build()
+// anything left unset is the same as in VM::new()
+#[derive(Default)]
+pub struct VmBuilder {
+    seed: Option<u64>,
+    quirks: Quirks,
+    hires: bool,
+    screen: Option<Box<dyn Screen>>,
+    keypad: Option<Box<dyn Keypad>>,
+    buzzer: Option<Box<dyn Buzzer>>,
+}
+
+impl VmBuilder {
+    pub fn new() -> VmBuilder {
+        VmBuilder::default()
+    }
+
+    // without a seed the rng is seeded from the clock
+    pub fn seed(mut self, seed: u64) -> VmBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> VmBuilder {
+        self.quirks = quirks;
+        self
+    }
+
+    // start in the SUPER-CHIP 128x64 mode
+    pub fn hires(mut self, hires: bool) -> VmBuilder {
+        self.hires = hires;
+        self
+    }
+
+    pub fn screen(mut self, screen: Box<dyn Screen>) -> VmBuilder {
+        self.screen = Some(screen);
+        self
+    }
+
+    pub fn keypad(mut self, keypad: Box<dyn Keypad>) -> VmBuilder {
+        self.keypad = Some(keypad);
+        self
+    }
+
+    pub fn buzzer(mut self, buzzer: Box<dyn Buzzer>) -> VmBuilder {
+        self.buzzer = Some(buzzer);
+        self
+    }
+
+    pub fn build(self) -> VM {
+        let mut vm = match self.seed {
+            Some(seed) => VM::new_with_seed(seed),
+            None => VM::new(),
+        };
+        vm.quirks = self.quirks;
+        vm.set_hires(self.hires);
+        if let Some(screen) = self.screen {
+            vm.set_screen(screen);
+        }
+        if let Some(keypad) = self.keypad {
+            vm.set_keypad(keypad);
+        }
+        if let Some(buzzer) = self.buzzer {
+            vm.set_buzzer(buzzer);
+        }
+        vm
+    }
+}
+
+// save states
+// the machine state is copied into a serde friendly shape, the rng and the debugging aids are left out
+// and a restored VM starts with a freshly seeded rng
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Snapshot {
+    memory: Vec<u8>,
+    registers: [u8; REGISTER_COUNT],
+    i: u16,
+    dt: u8,
+    st: u8,
+    pc: u16,
+    sp: u8,
+    stack: [u16; STACK_LENGTH],
+    display: Vec<bool>, // row by row, display_width() pixels each
+    hires: bool,
+    keys: [bool; KEY_COUNT],
+    quirks: Quirks,
+    rom: Vec<u8>,
+    rom_start: u16,
+    font_base: u16,
+}
+
+#[cfg(feature = "serde")]
+impl VM {
+    pub fn snapshot(&self) -> Vec<u8> {
+        let snapshot = Snapshot {
+            memory: self.memory.to_vec(),
+            registers: self.registers,
+            i: self.i,
+            dt: self.dt,
+            st: self.st,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            display: self.display.iter().flatten().copied().collect(),
+            hires: self.hires,
+            keys: std::array::from_fn(|key| self.pheriphal.keypad.is_down(key as u8)),
+            quirks: self.quirks,
+            rom: self.rom.clone(),
+            rom_start: self.rom_start,
+            font_base: self.font_base,
+        };
+        bincode::serialize(&snapshot).expect("a snapshot always serializes")
+    }
+
+    pub fn restore(bytes: &[u8]) -> anyhow::Result<VM> {
+        let snapshot: Snapshot = bincode::deserialize(bytes)?;
+        let mut vm = VM::with_quirks(snapshot.quirks);
+        vm.set_hires(snapshot.hires);
+        anyhow::ensure!(
+            snapshot.memory.len() == MEMORY_BYTES
+                && snapshot.display.len() == vm.display_width() * vm.display_height()
+                && snapshot.sp as usize <= STACK_LENGTH
+                && snapshot.rom_start as usize + snapshot.rom.len() <= MEMORY_BYTES
+                && snapshot.font_base as usize + FONT_AREA_BYTES <= PROGRAM_START,
+            "snapshot does not describe a valid machine state"
+        );
+
+        vm.memory.copy_from_slice(&snapshot.memory);
+        vm.registers = snapshot.registers;
+        vm.i = snapshot.i;
+        vm.dt = snapshot.dt;
+        vm.set_sound_timer(snapshot.st);
+        vm.pc = snapshot.pc;
+        vm.sp = snapshot.sp;
+        vm.stack = snapshot.stack;
+        let width = vm.display_width();
+        vm.display = snapshot
+            .display
+            .chunks(width)
+            .map(<[bool]>::to_vec)
+            .collect();
+        vm.sync_screen();
+        for (key, &down) in snapshot.keys.iter().enumerate() {
+            vm.set_key(key as u8, down);
+        }
+        vm.rom = snapshot.rom;
+        vm.rom_start = snapshot.rom_start;
+        vm.font_base = snapshot.font_base;
+        Ok(vm)
+    }
+
+    // writes a snapshot to a file behind a header of "CH8S" and the FNV-1a checksum of the loaded ROM
+    pub fn save_state<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut bytes = SAVE_STATE_MAGIC.to_vec();
+        bytes.extend_from_slice(&fnv1a(&self.rom).to_le_bytes());
+        bytes.extend_from_slice(&self.snapshot());
+        std::fs::write(path, bytes)?;
         Ok(())
     }
 
-    fn add_i(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx1E - ADD I, Vx
-        Set I = I + Vx.
-        The values of I and Vx are added, and the results are stored in I.
-        */
-        self.i = self.i + self.registers[x.0 as usize] as u16;
+    // reads a file written by save_state back into this VM, which must have the same ROM loaded,
+    // a state saved with another ROM is rejected with VmError::SaveStateRomMismatch
+    // the screen, keypad, buzzer and callbacks stay plugged in and are brought up to date,
+    // the rng and the debugging aids start over as with restore
+    pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> anyhow::Result<()> {
+        let bytes = std::fs::read(path)?;
+        let header = SAVE_STATE_MAGIC.len() + 4;
+        anyhow::ensure!(
+            bytes.len() >= header && bytes.starts_with(SAVE_STATE_MAGIC),
+            "not a save state file"
+        );
+        let saved_rom = u32::from_le_bytes(bytes[SAVE_STATE_MAGIC.len()..header].try_into()?);
+        let loaded_rom = fnv1a(&self.rom);
+        if saved_rom != loaded_rom {
+            return Err(VmError::SaveStateRomMismatch(saved_rom, loaded_rom).into());
+        }
+
+        let mut restored = VM::restore(&bytes[header..])?;
+        let keys: [bool; KEY_COUNT] =
+            std::array::from_fn(|key| restored.pheriphal.keypad.is_down(key as u8));
+        let st = restored.st;
+        restored.st = self.st;
+        std::mem::swap(&mut restored.pheriphal, &mut self.pheriphal);
+        *self = restored;
+        for (key, &down) in keys.iter().enumerate() {
+            self.set_key(key as u8, down);
+        }
+        self.sync_screen();
+        self.set_sound_timer(st);
         Ok(())
     }
+}
 
-    fn load_sprite(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx29 - LD F, Vx
-        Set I = location of sprite for digit Vx.
-        The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx.
-        See section 2.4, Display, for more information on the Chip-8 hexadecimal font.
-        */
+// side effect recording
+impl VM {
+    // executes op like execute() and reports the registers and memory it touched
+    pub fn execute_recorded(&mut self, op: OpCode) -> anyhow::Result<SideEffects> {
+        let mut effects = self.planned_effects(&op);
+        let pc = self.pc;
+        let waiting_for_key = matches!(op, OpCode::LoadKey(_));
+        self.execute(op)?;
+        effects.pc_delta = self.pc as i32 - pc as i32;
+        // Fx0A with no key pressed moves pc back onto itself and leaves Vx alone
+        if waiting_for_key && effects.pc_delta == -2 {
+            effects.registers_written.clear();
+        }
+        Ok(effects)
+    }
 
-        todo!()
+    // the read/write sets are derived from the opcode and the current I rather than by diffing state,
+    // so a write that stores the same value is still reported; writes that depend on how the
+    // instruction turned out are corrected by execute_recorded afterwards
+    fn planned_effects(&self, op: &OpCode) -> SideEffects {
+        let span = |len: u16| -> Vec<u16> {
+            (0..len)
+                .map(|o| self.wrap_addr(self.i.wrapping_add(o) as usize) as u16)
+                .collect()
+        };
+        let mut e = SideEffects::default();
+        match op {
+            OpCode::System(_)
+            | OpCode::ClearScreen
+            | OpCode::Return
+            | OpCode::Jump(_)
+            | OpCode::Call(_) => {}
+            OpCode::SkipEqual(x, _) | OpCode::SkipNotEqual(x, _) => e.registers_read = vec![x.0],
+            OpCode::SkipEqualRegister(x, y) | OpCode::SkipNotEqualRegister(x, y) => {
+                e.registers_read = vec![x.0, y.0];
+            }
+            OpCode::Load(x, _) | OpCode::Random(x, _) => e.registers_written = vec![x.0],
+            OpCode::Add(x, _) => {
+                e.registers_read = vec![x.0];
+                e.registers_written = vec![x.0];
+            }
+            OpCode::LoadRegister(x, y) => {
+                e.registers_read = vec![y.0];
+                e.registers_written = vec![x.0];
+            }
+            OpCode::OrRegister(x, y) | OpCode::AndRegister(x, y) | OpCode::XorRegister(x, y) => {
+                e.registers_read = vec![x.0, y.0];
+                e.registers_written = vec![x.0];
+            }
+            OpCode::AddRegister(x, y)
+            | OpCode::SubRegister(x, y)
+            | OpCode::SubNotBorrowRegister(x, y) => {
+                e.registers_read = vec![x.0, y.0];
+                e.registers_written = vec![x.0, 0x0f];
+            }
+            OpCode::ShrRegister(x, y) | OpCode::ShlRegister(x, y) => {
+                e.registers_read = vec![if self.quirks.shift_uses_vy { y.0 } else { x.0 }];
+                e.registers_written = vec![x.0, 0x0f];
+            }
+            OpCode::Set(_) => e.i_written = true,
+            OpCode::JumpV0(_) => e.registers_read = vec![0],
+            OpCode::Draw(x, y, nibble) => {
+                e.registers_read = vec![x.0, y.0];
+                e.registers_written = vec![0x0f];
+                e.memory_read = span(self.sprite_len(*nibble) as u16);
+            }
+            OpCode::SkipKey(x)
+            | OpCode::SkipNotKey(x)
+            | OpCode::SetDelayTimer(x)
+            | OpCode::SetSoundTimer(x) => e.registers_read = vec![x.0],
+            OpCode::LoadDelayTimer(x) | OpCode::LoadKey(x) => e.registers_written = vec![x.0],
+            OpCode::AddI(x) => {
+                e.registers_read = vec![x.0];
+                e.i_written = true;
+                if self.quirks.add_i_sets_vf {
+                    e.registers_written = vec![0x0f];
+                }
+            }
+            OpCode::LoadSprite(x) | OpCode::LoadLargeSprite(x) => {
+                e.registers_read = vec![x.0];
+                e.i_written = true;
+            }
+            OpCode::LoadBCD(x) => {
+                e.registers_read = vec![x.0];
+                e.memory_written = span(3);
+            }
+            OpCode::SaveRegisters(x) => {
+                e.registers_read = (0..=x.0).collect();
+                e.memory_written = span(x.0 as u16 + 1);
+                e.i_written = self.quirks.memory_index != MemoryIndex::Unchanged;
+            }
+            OpCode::LoadRegisters(x) => {
+                e.registers_written = (0..=x.0).collect();
+                e.memory_read = span(x.0 as u16 + 1);
+                e.i_written = self.quirks.memory_index != MemoryIndex::Unchanged;
+            }
+            OpCode::ScrollDown(_)
+            | OpCode::ScrollUp(_)
+            | OpCode::ScrollRight
+            | OpCode::ScrollLeft
+            | OpCode::LowRes
+            | OpCode::HighRes => {}
+        }
+        e
     }
+}
 
-    fn load_bcd(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx33 - LD B, Vx
-        Store BCD representation of Vx in memory locations I, I+1, and I+2.
-        The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.
-        */
-        todo!()
+// debugging aids
+impl VM {
+    // patches a running program: assembles one instruction per line of source and writes the words to addr,
+    // returns the number of bytes written. Blank lines are skipped, and nothing is written unless
+    // every line assembles and the code fits below 0x1000
+    pub fn assemble_into(&mut self, addr: u16, source: &str) -> Result<usize, AssembleError> {
+        let mut bytes = Vec::new();
+        for line in source.lines().filter(|line| !line.trim().is_empty()) {
+            bytes.extend_from_slice(&u16::from(OpCode::try_from(line)?).to_be_bytes());
+        }
+        let start = addr as usize;
+        if start + bytes.len() > MEMORY_BYTES {
+            return Err(AssembleError::DoesNotFit(bytes.len(), addr));
+        }
+        self.memory[start..start + bytes.len()].copy_from_slice(&bytes);
+        Result::Ok(bytes.len())
     }
 
-    fn save_registers(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx55 - LD [I], Vx
-        Store registers V0 through Vx in memory starting at location I.
-        The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
-        */
-        for (offset, index) in (0..=x.0).enumerate() {
-            self.memory[self.i as usize + offset] = self.registers[index as usize];
+    // runs the CALL at pc until its subroutine returns to the instruction after it,
+    // any other instruction is a single tick
+    pub fn step_over(&mut self, max_cycles: u32) -> anyhow::Result<Stopped> {
+        let depth = self.sp;
+        if matches!(self.instruction_at(self.pc)?, OpCode::Call(_)) {
+            self.step_until(max_cycles, |vm| vm.sp <= depth)
+        } else {
+            self.step_until(max_cycles, |_| true)
         }
-        Ok(())
     }
 
-    fn load_registers(&mut self, x: V) -> anyhow::Result<()> {
-        /* Fx65 - LD Vx, [I]
-        Read registers V0 through Vx from memory starting at location I.
-        The interpreter reads values from memory starting at location I into registers V0 through Vx.
-        */
-        for (offset, index) in (0..=x.0).enumerate() {
-            self.registers[index as usize] = self.memory[self.i as usize + offset];
+    // runs until the subroutine pc is in returns to its caller
+    pub fn step_out(&mut self, max_cycles: u32) -> anyhow::Result<Stopped> {
+        let depth = self.sp;
+        if depth == 0 {
+            return Err(VmError::StackUnderflow.into());
+        }
+        self.step_until(max_cycles, |vm| vm.sp < depth)
+    }
+
+    // ticks like run() until done holds after a tick, stopping early on breakpoints and halts
+    fn step_until(
+        &mut self,
+        max_cycles: u32,
+        done: impl Fn(&VM) -> bool,
+    ) -> anyhow::Result<Stopped> {
+        for n in 0..max_cycles {
+            if n > 0 && !self.is_stalled() && self.breakpoints.contains(&self.pc) {
+                return Ok(Stopped::Breakpoint(self.pc));
+            }
+            self.tick_with_timers()?;
+            if done(self) {
+                return Ok(Stopped::Stepped);
+            }
+            if self.is_halted() {
+                return Ok(Stopped::Halted);
+            }
+        }
+        Ok(Stopped::Budget)
+    }
+
+    // makes run() stop with Stopped::RanOffTheRails after threshold 0000 words in a row,
+    // SYS 0 does nothing, so a long run of them is zeroed RAM rather than code; None turns it off
+    pub fn detect_noop_sled(&mut self, threshold: Option<usize>) {
+        self.sled_threshold = threshold;
+    }
+
+    fn ran_off_the_rails(&self) -> bool {
+        self.sled_threshold
+            .is_some_and(|threshold| self.sled_length >= threshold)
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn track_uninitialized_reads(&mut self, enabled: bool) {
+        self.track_uninitialized = enabled;
+    }
+
+    // hands over the warnings collected so far and clears them
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    // starts or stops collecting TraceEntry records in tick(), entries collected so far are kept
+    pub fn enable_trace(&mut self, enabled: bool) {
+        self.tracing = enabled;
+    }
+
+    // how many of the most recent instructions the trace keeps, 256 by default
+    pub fn set_trace_capacity(&mut self, entries: usize) {
+        self.trace_capacity = entries;
+        let excess = self.trace.len().saturating_sub(entries);
+        self.trace.drain(..excess);
+    }
+
+    // for triaging a broken ROM in one run: with diagnostics on, tick() treats a word that does not decode
+    // as a no-op and notes it with its address instead of failing; running off the end of memory still fails
+    pub fn collect_diagnostics(&mut self, enabled: bool) {
+        self.diagnosing = enabled;
+    }
+
+    // hands over the (address, error) pairs noted so far and clears them
+    pub fn drain_diagnostics(&mut self) -> Vec<(u16, DecodeError)> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    // with recording on every DRW also keeps the (x, y) of each pixel it erased, the ones that set VF
+    pub fn record_collisions(&mut self, enabled: bool) {
+        self.recording_collisions = enabled;
+        self.last_collision.clear();
+    }
+
+    // the pixels the last DRW erased, empty unless record_collisions is on
+    pub fn last_collision(&self) -> &[(usize, usize)] {
+        &self.last_collision
+    }
+
+    // only instructions whose OpCategory is in mask are traced, e.g.
+    // OpCategory::FlowControl.mask() | OpCategory::Display.mask(); OpCategory::ALL traces everything again
+    pub fn set_trace_filter(&mut self, mask: u16) {
+        self.trace_filter = mask;
+    }
+
+    // oldest first
+    pub fn trace(&self) -> &[TraceEntry] {
+        let start = self.trace.len().saturating_sub(self.trace_capacity);
+        &self.trace[start..]
+    }
+
+    // op is the instruction tick() just decoded at pc
+    fn record_trace(&mut self, op: &OpCode) {
+        if self.trace_capacity == 0 {
+            return;
+        }
+        let pc = self.pc as usize;
+        self.trace.push(TraceEntry {
+            pc: self.pc,
+            opcode_word: u16::from_be_bytes([self.memory[pc], self.memory[pc + 1]]),
+            mnemonic: op.clone().into(),
+            registers_before: self.registers,
+        });
+        if self.trace.len() >= 2 * self.trace_capacity {
+            self.trace.drain(..self.trace_capacity);
+        }
+    }
+
+    // addr is where op was fetched from, the address the warnings point at
+    fn check_uninitialized_reads(&mut self, op: &OpCode, addr: u16) {
+        let effects = self.planned_effects(op);
+        for r in effects.registers_read {
+            if !self.written_registers[r as usize] && self.reported_reads.insert((addr, r)) {
+                self.warnings.push(format!(
+                    "{:#06x}: V{:X} is read before being written",
+                    addr, r
+                ));
+            }
+        }
+        for w in effects.registers_written {
+            self.written_registers[w as usize] = true;
+        }
+    }
+}
+
+// implementation for opcodes
+impl VM {
+    // every memory access an opcode makes through I goes through these two,
+    // so a ROM pointing I past 0xFFF wraps or gets an error, see Quirks::memory_wrap, instead of crashing the host
+    fn read_mem(&self, addr: usize) -> anyhow::Result<u8> {
+        let addr = self.wrap_addr(addr);
+        match self.memory.get(addr) {
+            Some(&byte) => Ok(byte),
+            None => Err(VmError::MemoryAccess(addr).into()),
+        }
+    }
+
+    fn write_mem(&mut self, addr: usize, value: u8) -> anyhow::Result<()> {
+        let addr = self.wrap_addr(addr);
+        if self.quirks.protect_interpreter_region && addr < PROGRAM_START {
+            return Err(VmError::WriteToReserved(addr).into());
+        }
+        match self.memory.get_mut(addr) {
+            Some(byte) => *byte = value,
+            None => return Err(VmError::MemoryAccess(addr).into()),
         }
         Ok(())
     }
+
+    fn system(&mut self, nnn: Addr) -> anyhow::Result<()> {
+        /* 0nnn - SYS addr
+        Jump to a machine code routine at nnn.
+        This instruction is only used on the old computers on which Chip-8 was originally implemented.
+        It is ignored by modern interpreters.
+        */
+        Ok(())
+    }
+
+    fn clearscreen(&mut self) -> anyhow::Result<()> {
+        /* 00E0 - CLS
+        Clear the display.
+        */
+        let changed = self.display.iter().flatten().any(|&pixel| pixel);
+        self.clear_display();
+        if changed {
+            self.notify_draw();
+        }
+        if !self.blank_presented {
+            self.dirty = true;
+            self.blank_presented = true;
+        }
+        Ok(())
+    }
+
+    fn wrap_addr(&self, addr: usize) -> usize {
+        if self.quirks.memory_wrap {
+            addr % MEMORY_BYTES
+        } else {
+            addr
+        }
+    }
+
+    fn execute_return(&mut self) -> anyhow::Result<()> {
+        /* 00EE - RET
+        Return from a subroutine.
+        The interpreter sets the program counter to the address at the top of the stack,
+        then subtracts 1 from the stack pointer.
+        */
+        // sp counts the occupied levels, so the top of the stack is stack[sp - 1]
+        if self.sp == 0 {
+            return Err(VmError::StackUnderflow.into());
+        }
+        self.sp -= 1;
+        self.pc = self.stack[self.sp as usize];
+        Ok(())
+    }
+
+    fn jump(&mut self, nnn: Addr) -> anyhow::Result<()> {
+        /* 1nnn - JP addr
+        Jump to location nnn.
+        The interpreter sets the program counter to nnn.
+        */
+        self.pc = nnn.0;
+        Ok(())
+    }
+
+    fn execute_call(&mut self, nnn: Addr) -> anyhow::Result<()> {
+        /* 2nnn - CALL addr
+        Call subroutine at nnn.
+        The interpreter increments the stack pointer, then puts the current PC on the top of the stack.
+        The PC is then set to nnn.
+        */
+        if self.sp as usize == STACK_LENGTH {
+            return Err(VmError::StackOverflow.into());
+        }
+        self.stack[self.sp as usize] = self.pc;
+        self.sp += 1;
+        self.pc = nnn.0;
+        Ok(())
+    }
+
+    fn skip_equal(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
+        /* 3xkk - SE Vx, byte
+        Skip next instruction if Vx = kk.
+        The interpreter compares register Vx to kk, and if they are equal, increments the program counter by 2.
+        */
+        if self.registers[x.0 as usize] == kk.0 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn skip_not_equal(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
+        /* 4xkk - SNE Vx, byte
+        Skip next instruction if Vx != kk.
+        The interpreter compares register Vx to kk, and if they are not equal, increments the program counter by 2.
+        */
+        if self.registers[x.0 as usize] != kk.0 {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn skip_equal_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 5xy0 - SE Vx, Vy
+        Skip next instruction if Vx = Vy.
+        The interpreter compares register Vx to register Vy, and if they are equal, increments the program counter by 2.
+        */
+        if self.registers[x.0 as usize] == self.registers[y.0 as usize] {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    // todo 似乎不叫 load，应该叫 set_literal?
+    fn load(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
+        /* 6xkk - LD Vx, byte
+        Set Vx = kk.
+        The interpreter puts the value kk into register Vx.
+        */
+        self.registers[x.0 as usize] = kk.0;
+        Ok(())
+    }
+
+    fn add(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
+        /* 7xkk - ADD Vx, byte
+        Set Vx = Vx + kk.
+        Adds the value kk to the value of register Vx, then stores the result in Vx.
+        */
+        self.registers[x.0 as usize] = self.registers[x.0 as usize].wrapping_add(kk.0);
+        Ok(())
+    }
+
+    // todo 似乎也应该叫 set，copy 之类的？
+    fn load_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy0 - LD Vx, Vy
+        Set Vx = Vy.
+        Stores the value of register Vy in register Vx.
+        */
+        self.registers[x.0 as usize] = self.registers[y.0 as usize];
+        Ok(())
+    }
+
+    fn or_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy1 - OR Vx, Vy
+        Set Vx = Vx OR Vy.
+        Performs a bitwise OR on the values of Vx and Vy, then stores the result in Vx.
+        A bitwise OR compares the corrseponding bits from two values, and if either bit is 1,
+        then the same bit in the result is also 1. Otherwise, it is 0.
+        */
+
+        self.registers[x.0 as usize] = self.registers[x.0 as usize] | self.registers[y.0 as usize];
+        Ok(())
+    }
+
+    fn and_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy2 - AND Vx, Vy
+        Set Vx = Vx AND Vy.
+        Performs a bitwise AND on the values of Vx and Vy, then stores the result in Vx.
+        A bitwise AND compares the corrseponding bits from two values, and if both bits are 1,
+         then the same bit in the result is also 1. Otherwise, it is 0.
+        */
+        self.registers[x.0 as usize] = self.registers[x.0 as usize] & self.registers[y.0 as usize];
+        Ok(())
+    }
+
+    fn xor_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy3 - XOR Vx, Vy
+        Set Vx = Vx XOR Vy.
+        Performs a bitwise exclusive OR on the values of Vx and Vy, then stores the result in Vx. An exclusive OR compares the corrseponding bits from two values, and if the bits are not both the same, then the corresponding bit in the result is set to 1. Otherwise, it is 0.
+        */
+        self.registers[x.0 as usize] = self.registers[x.0 as usize] ^ self.registers[y.0 as usize];
+        Ok(())
+    }
+
+    fn add_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy4 - ADD Vx, Vy
+        Set Vx = Vx + Vy, set VF = carry.
+        The values of Vx and Vy are added together. If the result is greater than 8 bits (i.e., > 255,) VF is set to 1, otherwise 0. Only the lowest 8 bits of the result are kept, and stored in Vx.
+        */
+        let (r, overflow) =
+            self.registers[x.0 as usize].overflowing_add(self.registers[y.0 as usize]);
+        let carry = u8::from(overflow);
+        // VF is written last so the flag survives when x is VF, same in the other flag-setting opcodes
+        self.registers[x.0 as usize] = r;
+        self.registers[0x0f] = carry;
+        Ok(())
+    }
+
+    fn sub_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy5 - SUB Vx, Vy
+        Set Vx = Vx - Vy, set VF = NOT borrow.
+        If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from Vx, and the results stored in Vx.
+        */
+        let not_borrow = u8::from(self.registers[x.0 as usize] > self.registers[y.0 as usize]);
+        self.registers[x.0 as usize] =
+            self.registers[x.0 as usize].wrapping_sub(self.registers[y.0 as usize]);
+        self.registers[0x0f] = not_borrow;
+        Ok(())
+    }
+
+    fn shr_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy6 - SHR Vx {, Vy}
+        Set Vx = Vx SHR 1.
+        If the least-significant bit of Vx is 1, then VF is set to 1, otherwise 0. Then Vx is divided by 2.
+        */
+        self.quirk_usage.shift_source |= x != y;
+        let source = if self.quirks.shift_uses_vy { y.0 } else { x.0 };
+        let value = self.registers[source as usize];
+        let lsb = value & 0b0000_0001;
+        self.registers[x.0 as usize] = value >> 1;
+        self.registers[0x0f] = lsb;
+        Ok(())
+    }
+
+    fn sub_not_borrow_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xy7 - SUBN Vx, Vy
+        Set Vx = Vy - Vx, set VF = NOT borrow.
+        If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from Vy, and the results stored in Vx.
+        */
+        let not_borrow = u8::from(self.registers[y.0 as usize] > self.registers[x.0 as usize]);
+        self.registers[x.0 as usize] =
+            self.registers[y.0 as usize].wrapping_sub(self.registers[x.0 as usize]);
+        self.registers[0x0f] = not_borrow;
+        Ok(())
+    }
+
+    fn shl_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 8xyE - SHL Vx {, Vy}
+        Set Vx = Vx SHL 1.
+        If the most-significant bit of Vx is 1, then VF is set to 1, otherwise to 0. Then Vx is multiplied by 2.
+        */
+        self.quirk_usage.shift_source |= x != y;
+        let source = if self.quirks.shift_uses_vy { y.0 } else { x.0 };
+        let value = self.registers[source as usize];
+        let msb = u8::from(value & 0b1000_0000 != 0);
+        self.registers[x.0 as usize] = value << 1;
+        self.registers[0x0f] = msb;
+        Ok(())
+    }
+
+    fn skip_not_equal_register(&mut self, x: V, y: V) -> anyhow::Result<()> {
+        /* 9xy0 - SNE Vx, Vy
+        Skip next instruction if Vx != Vy.
+        The values of Vx and Vy are compared, and if they are not equal, the program counter is increased by 2.
+        */
+        if self.registers[x.0 as usize] != self.registers[y.0 as usize] {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, nnn: Addr) -> anyhow::Result<()> {
+        /* Annn - LD I, addr
+        Set I = nnn.
+        The value of register I is set to nnn.
+        */
+        self.i = nnn.0;
+        Ok(())
+    }
+
+    fn jump_v0(&mut self, nnn: Addr) -> anyhow::Result<()> {
+        /* Bnnn - JP V0, addr
+        Jump to location nnn + V0.
+        The program counter is set to nnn plus the value of V0.
+        */
+        // addresses are 12 bits, a target past 0xFFF wraps back to the start of memory
+        self.pc = (self.registers[0] as u16).wrapping_add(nnn.0) & 0x0FFF;
+        Ok(())
+    }
+
+    fn execute_random(&mut self, x: V, kk: Byte) -> anyhow::Result<()> {
+        /* Cxkk - RND Vx, byte
+        Set Vx = random byte AND kk.
+        The interpreter generates a random number from 0 to 255, which is then ANDed with the value kk. The results are stored in Vx. See instruction 8xy2 for more information on AND.
+        */
+        let r = self.pheriphal.random_device.next_u8();
+        self.registers[x.0 as usize] = r & kk.0;
+        Ok(())
+    }
+
+    fn draw(&mut self, x: V, y: V, nibble: u8) -> anyhow::Result<()> {
+        /* Dxyn - DRW Vx, Vy, nibble
+        Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+        The interpreter reads n bytes from memory, starting at the address stored in I. These bytes are then displayed as sprites on screen at coordinates (Vx, Vy). Sprites are XORed onto the existing screen. If this causes any pixels to be erased, VF is set to 1, otherwise it is set to 0. If the sprite is positioned so part of it is outside the coordinates of the display, it wraps around to the opposite side of the screen. See instruction 8xy3 for more information on XOR, and section 2.4, Display, for more information on the Chip-8 screen and sprites.
+        */
+        let (wrap_x, wrap_y) = self.draw_origin_wrap();
+        let origin_x = self.registers[x.0 as usize] as usize % wrap_x;
+        let origin_y = self.registers[y.0 as usize] as usize % wrap_y;
+        let bytes_per_row = self.sprite_bytes_per_row(nibble);
+        if self.quirks.strict_draw_source
+            && (self.i as usize) < PROGRAM_START
+            && !self.is_font_glyph(self.i)
+        {
+            return Err(VmError::DrawFromReserved(self.i).into());
+        }
+        // read the whole sprite first so an out of bounds row leaves the display untouched
+        let sprite_bytes = (0..self.sprite_len(nibble))
+            .map(|offset| self.read_mem(self.i as usize + offset))
+            .collect::<anyhow::Result<Vec<u8>>>()?;
+        let (width, height) = self.screen_dimensions();
+        let rows = sprite_bytes.len() / bytes_per_row;
+        self.quirk_usage.display_wait = true;
+        self.quirk_usage.clipping |=
+            origin_x + bytes_per_row * 8 > width || origin_y + rows > height;
+        let clip = self.quirks.clip_sprites_with_counting;
+        self.last_collision.clear();
+        let mut collided_rows = 0;
+        let mut clipped_rows = 0;
+        for (row, bytes) in sprite_bytes.chunks(bytes_per_row).enumerate() {
+            if clip && origin_y + row >= height {
+                clipped_rows += 1;
+                continue;
+            }
+            let py = (origin_y + row) % height;
+            let mut collision = false;
+            for (column, &sprite) in bytes.iter().enumerate() {
+                let left = origin_x + column * 8;
+                if clip && left >= width {
+                    continue;
+                }
+                for bit in 0..8 {
+                    if sprite & (0x80 >> bit) == 0 || clip && left + bit >= width {
+                        continue;
+                    }
+                    let px = (left + bit) % width;
+                    if self.display[py][px] {
+                        collision = true;
+                        if self.recording_collisions {
+                            self.last_collision.push((px, py));
+                        }
+                    }
+                    self.display[py][px] = !self.display[py][px];
+                    self.pheriphal.screen.draw_pixel(px, py, true);
+                }
+            }
+            collided_rows += u8::from(collision);
+        }
+        self.registers[0x0f] = if clip {
+            collided_rows + clipped_rows
+        } else {
+            u8::from(collided_rows > 0)
+        };
+        self.feed_intensity();
+        // every set sprite bit flips a pixel, so only an all-zero sprite leaves the display as it was
+        if sprite_bytes.iter().any(|&sprite| sprite != 0) {
+            self.notify_draw();
+        }
+        Ok(())
+    }
+
+    // true when addr is where Fx29 or Fx30 points I for one of the digits
+    fn is_font_glyph(&self, addr: u16) -> bool {
+        let addr = addr as usize;
+        let small = self.font_base as usize..self.large_font_base();
+        let large = self.large_font_base()
+            ..self.large_font_base() + LARGE_FONT.len() * LARGE_FONT_SPRITE_BYTES;
+        small.contains(&addr) && (addr - small.start).is_multiple_of(FONT_SPRITE_BYTES)
+            || large.contains(&addr) && (addr - large.start).is_multiple_of(LARGE_FONT_SPRITE_BYTES)
+    }
+
+    // (x, y) moduli Vx and Vy are wrapped by before drawing, see Quirks::draw_origin_wrap
+    fn draw_origin_wrap(&self) -> (usize, usize) {
+        self.quirks
+            .draw_origin_wrap
+            .filter(|&(x, y)| x > 0 && y > 0)
+            .unwrap_or(self.screen_dimensions())
+    }
+
+    // Dxy0 is a 16x16 sprite of two bytes per row in the SUPER-CHIP high resolution mode,
+    // in the classic 64x32 mode it has zero rows, so it draws nothing and VF ends up 0
+    fn sprite_bytes_per_row(&self, nibble: u8) -> usize {
+        if nibble == 0 && self.hires {
+            2
+        } else {
+            1
+        }
+    }
+
+    // how many bytes from I a Dxyn reads
+    fn sprite_len(&self, nibble: u8) -> usize {
+        if nibble == 0 && self.hires {
+            32
+        } else {
+            nibble as usize
+        }
+    }
+
+    fn key(&mut self, x: V) -> anyhow::Result<()> {
+        /* Ex9E - SKP Vx
+        Skip next instruction if key with the value of Vx is pressed.
+        Checks the keyboard, and if the key corresponding to the value of Vx is currently in the down position, PC is increased by 2.
+        */
+        let key = self.registers[x.0 as usize] & 0x0F;
+        if self.pheriphal.keypad.is_down(key) {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn skip_not_key(&mut self, x: V) -> anyhow::Result<()> {
+        /* ExA1 - SKNP Vx
+        Skip next instruction if key with the value of Vx is not pressed.
+        Checks the keyboard, and if the key corresponding to the value of Vx is currently in the up position, PC is increased by 2.
+        */
+        let key = self.registers[x.0 as usize] & 0x0F;
+        if !self.pheriphal.keypad.is_down(key) {
+            self.pc += 2;
+        }
+        Ok(())
+    }
+
+    fn load_dt(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx07 - LD Vx, DT
+        Set Vx = delay timer value.
+        The value of DT is placed into Vx.
+        */
+        self.registers[x.0 as usize] = self.dt;
+        Ok(())
+    }
+
+    fn load_key(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx0A - LD Vx, K
+        Wait for a key press, store the value of the key in Vx.
+        All execution stops until a key is pressed, then the value of that key is stored in Vx.
+        */
+        // waiting is modelled per tick: while the keypad has no key pc is moved back onto this instruction,
+        // so every tick re-executes it until the keypad reports one, which goes to Vx
+        let key = self
+            .batch_press
+            .take()
+            .or_else(|| self.pheriphal.keypad.wait_key());
+        match key {
+            Some(key) => self.registers[x.0 as usize] = key & 0x0F,
+            None => self.pc = self.pc.wrapping_sub(2),
+        }
+        Ok(())
+    }
+
+    fn set_dt(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx15 - LD DT, Vx
+        Set delay timer = Vx.
+        DT is set equal to the value of Vx.
+        */
+        self.dt = self.registers[x.0 as usize];
+        Ok(())
+    }
+
+    fn set_st(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx18 - LD ST, Vx
+        Set sound timer = Vx.
+        ST is set equal to the value of Vx.
+        */
+        self.set_sound_timer(self.registers[x.0 as usize]);
+        Ok(())
+    }
+
+    fn add_i(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx1E - ADD I, Vx
+        Set I = I + Vx.
+        The values of I and Vx are added, and the results are stored in I.
+        */
+        self.quirk_usage.add_i_vf = true;
+        let sum = self.i.wrapping_add(self.registers[x.0 as usize] as u16);
+        self.i = sum;
+        if self.quirks.add_i_sets_vf {
+            self.registers[0x0f] = u8::from(sum > 0x0FFF);
+        }
+        Ok(())
+    }
+
+    fn load_sprite(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx29 - LD F, Vx
+        Set I = location of sprite for digit Vx.
+        The value of I is set to the location for the hexadecimal sprite corresponding to the value of Vx.
+        See section 2.4, Display, for more information on the Chip-8 hexadecimal font.
+        */
+        let digit = (self.registers[x.0 as usize] & 0x0F) as usize;
+        self.i = self.font_base + (digit * FONT_SPRITE_BYTES) as u16;
+        Ok(())
+    }
+
+    fn load_large_sprite(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx30 - LD HF, Vx (SUPER-CHIP)
+        Set I = location of the large sprite for digit Vx.
+        The value of I is set to the location of the 8x10 sprite for the decimal digit in Vx.
+        */
+        // there are only ten large digits, other values wrap around instead of pointing past the font
+        let digit = self.registers[x.0 as usize] as usize % LARGE_FONT.len();
+        self.i = (self.large_font_base() + digit * LARGE_FONT_SPRITE_BYTES) as u16;
+        Ok(())
+    }
+
+    fn load_bcd(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx33 - LD B, Vx
+        Store BCD representation of Vx in memory locations I, I+1, and I+2.
+        The interpreter takes the decimal value of Vx, and places the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.
+        */
+        let i = self.i as usize;
+        let value = self.registers[x.0 as usize];
+        self.write_mem(i, value / 100)?;
+        self.write_mem(i + 1, value / 10 % 10)?;
+        self.write_mem(i + 2, value % 10)?;
+        Ok(())
+    }
+
+    fn save_registers(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx55 - LD [I], Vx
+        Store registers V0 through Vx in memory starting at location I.
+        The interpreter copies the values of registers V0 through Vx into memory, starting at the address in I.
+        */
+        for (offset, index) in (0..=x.0).enumerate() {
+            self.write_mem(self.i as usize + offset, self.registers[index as usize])?;
+        }
+        self.advance_memory_index(x);
+        Ok(())
+    }
+
+    // Fx55 and Fx65 move I the same way
+    fn advance_memory_index(&mut self, x: V) {
+        self.quirk_usage.memory_index = true;
+        let step = match self.quirks.memory_index {
+            MemoryIndex::Unchanged => 0,
+            MemoryIndex::IncrementByX => x.0 as u16,
+            MemoryIndex::IncrementPastX => x.0 as u16 + 1,
+        };
+        self.i = self.i.wrapping_add(step);
+    }
+
+    fn load_registers(&mut self, x: V) -> anyhow::Result<()> {
+        /* Fx65 - LD Vx, [I]
+        Read registers V0 through Vx from memory starting at location I.
+        The interpreter reads values from memory starting at location I into registers V0 through Vx.
+        */
+        for (offset, index) in (0..=x.0).enumerate() {
+            self.registers[index as usize] = self.read_mem(self.i as usize + offset)?;
+        }
+        self.advance_memory_index(x);
+        Ok(())
+    }
+
+    // shifts the display in place, flips the changed pixels on the screen
+    // and tells the draw callback when any pixel actually changed
+    fn scroll_display(&mut self, scroll: impl FnOnce(&mut [Vec<bool>])) {
+        let before = self.display.clone();
+        scroll(&mut self.display);
+        if self.display == before {
+            return;
+        }
+        for (y, (old, new)) in before.iter().zip(self.display.iter()).enumerate() {
+            for (x, (a, b)) in old.iter().zip(new.iter()).enumerate() {
+                if a != b {
+                    self.pheriphal.screen.draw_pixel(x, y, true);
+                }
+            }
+        }
+        self.notify_draw();
+    }
+
+    fn scroll_down(&mut self, nibble: u8) -> anyhow::Result<()> {
+        /* 00Cn - SCD nibble (SUPER-CHIP)
+        Scroll the display down by n pixels.
+        The rows scrolled in at the top are blank.
+        */
+        let n = nibble as usize;
+        self.scroll_display(|display| {
+            display.rotate_right(n);
+            display[..n].iter_mut().for_each(|row| row.fill(false));
+        });
+        Ok(())
+    }
+
+    fn scroll_up(&mut self, nibble: u8) -> anyhow::Result<()> {
+        /* 00Dn - SCU nibble (XO-CHIP)
+        Scroll the display up by n pixels.
+        The rows scrolled in at the bottom are blank.
+        */
+        let n = nibble as usize;
+        self.scroll_display(|display| {
+            display.rotate_left(n);
+            let height = display.len();
+            display[height - n..]
+                .iter_mut()
+                .for_each(|row| row.fill(false));
+        });
+        Ok(())
+    }
+
+    fn scroll_right(&mut self) -> anyhow::Result<()> {
+        /* 00FB - SCR (SUPER-CHIP)
+        Scroll the display right by 4 pixels.
+        The columns scrolled in at the left are blank.
+        */
+        self.scroll_display(|display| {
+            for row in display.iter_mut() {
+                row.rotate_right(4);
+                row[..4].fill(false);
+            }
+        });
+        Ok(())
+    }
+
+    fn scroll_left(&mut self) -> anyhow::Result<()> {
+        /* 00FC - SCL (SUPER-CHIP)
+        Scroll the display left by 4 pixels.
+        The columns scrolled in at the right are blank.
+        */
+        self.scroll_display(|display| {
+            for row in display.iter_mut() {
+                row.rotate_left(4);
+                let width = row.len();
+                row[width - 4..].fill(false);
+            }
+        });
+        Ok(())
+    }
+
+    // SUPER-CHIP implementations disagree on what happens to the picture on a mode switch,
+    // this one always starts the new mode with a blank display rather than rescaling the old one,
+    // the draw callback is told since the buffer changes size
+    fn low_res(&mut self) -> anyhow::Result<()> {
+        /* 00FE - LOW (SUPER-CHIP)
+        Switch to the 64x32 low resolution display.
+        The display is cleared.
+        */
+        self.set_hires(false);
+        self.notify_draw();
+        Ok(())
+    }
+
+    fn high_res(&mut self) -> anyhow::Result<()> {
+        /* 00FF - HIGH (SUPER-CHIP)
+        Switch to the 128x64 high resolution display.
+        The display is cleared.
+        */
+        self.set_hires(true);
+        self.notify_draw();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peek_decodes_pc_without_advancing() {
+        let mut vm = VM::new_with_seed(42);
+        vm.load_rom(&[0xD1, 0x25]).unwrap();
+        let (op, mnemonic) = vm.peek().unwrap();
+        assert_eq!(op, OpCode::Draw(V(1), V(2), 5));
+        assert_eq!(mnemonic, "DRW V1, V2, 5");
+        assert_eq!(vm.pc(), 0x200);
+    }
+
+    #[test]
+    fn instruction_at_decodes_any_address() {
+        let mut vm = VM::new_with_seed(42);
+        vm.memory[0x300] = 0x6A;
+        vm.memory[0x301] = 0x1F;
+        assert_eq!(
+            vm.instruction_at(0x300),
+            Result::Ok(OpCode::Load(V(0xA), Byte(0x1F)))
+        );
+        assert_eq!(vm.pc(), 0);
+        assert_eq!(
+            vm.instruction_at(0xFFF),
+            Err(DecodeError::AddressOutOfRange(0xFFF))
+        );
+        vm.memory[0x302] = 0x51;
+        vm.memory[0x303] = 0x21;
+        assert_eq!(
+            vm.instruction_at(0x302),
+            Err(DecodeError::ReservedBitsSet(0x5121))
+        );
+    }
+
+    #[test]
+    fn scroll_up_moves_pixels_and_blanks_the_bottom() {
+        let mut vm = VM::new_with_seed(42);
+        // a full row at y = 5 and another on the last row
+        vm.display[5][10] = true;
+        vm.display[DISPLAY_HEIGHT - 1].fill(true);
+        let op = OpCode::try_from(0x00D3).unwrap();
+        assert_eq!(op, OpCode::ScrollUp(3));
+        assert_eq!(op.tag(), 41);
+        vm.execute(op).unwrap();
+        assert!(vm.display[2][10]);
+        assert!(!vm.display[5][10]);
+        assert!(vm.display[DISPLAY_HEIGHT - 4].iter().all(|&lit| lit));
+        for row in &vm.display[DISPLAY_HEIGHT - 3..] {
+            assert!(row.iter().all(|&lit| !lit));
+        }
+        let lit: usize = vm.display.iter().flatten().filter(|&&lit| lit).count();
+        assert_eq!(lit, DISPLAY_WIDTH + 1);
+    }
+
+    #[test]
+    fn render_debug_shows_screen_and_registers() {
+        let mut vm = VM::new_with_seed(42);
+        // V0 = 0x2A, draw the 0 glyph at (0x2A, 0x2A)
+        vm.load_rom(&[0x60, 0x2A, 0xA0, 0x50, 0xD0, 0x05]).unwrap();
+        for _ in 0..3 {
+            vm.tick().unwrap();
+        }
+        let out = vm.render_debug();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), DISPLAY_HEIGHT);
+        assert!(out.contains('█'));
+        assert!(lines[0].ends_with(" | V0: 0x2a"));
+        assert!(out.contains("VF: 0x00"));
+        assert!(out.contains("I:  0x050"));
+        assert!(out.contains("PC: 0x206"));
+        assert!(out.contains("SP: 0"));
+        assert!(out.contains("DT: 0x00"));
+        assert!(out.contains("ST: 0x00"));
+    }
+
+    #[test]
+    fn fixed_sequence_rng_drives_random() {
+        use crate::interp::rng::FixedSequence;
+
+        let mut vm = VM::with_rng(Box::new(FixedSequence(vec![0x12, 0xF0, 0xFF])));
+        vm.execute(OpCode::Random(V(0), Byte(0xFF))).unwrap();
+        vm.execute(OpCode::Random(V(1), Byte(0x0F))).unwrap();
+        vm.execute(OpCode::Random(V(2), Byte(0xAA))).unwrap();
+        vm.execute(OpCode::Random(V(3), Byte(0xFF))).unwrap();
+        assert_eq!(&vm.registers()[..4], &[0x12, 0x00, 0xAA, 0x12]);
+    }
+
+    #[test]
+    fn run_stops_on_a_jump_to_itself() {
+        let mut vm = VM::new_with_seed(42);
+        // LD V0, 1 ; LD V1, 2 ; JP 0x204
+        vm.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x12, 0x04]).unwrap();
+        let report = vm.run(1000).unwrap();
+        assert_eq!(report.executed, 3);
+        assert_eq!(report.stopped, Stopped::Halted);
+        assert!(vm.is_halted());
+        assert_eq!(vm.pc(), 0x204);
+    }
+
+    #[test]
+    fn run_steps_timers_across_short_budgets() {
+        let mut vm = VM::new_with_seed(42);
+        // CLS ; JP 0x200, a loop that never halts since the jump does not target itself
+        vm.load_rom(&[0x00, 0xE0, 0x12, 0x00]).unwrap();
+        vm.set_delay_timer_value(60);
+        for _ in 0..100 {
+            vm.run(5).unwrap();
+        }
+        assert_eq!(vm.dt, 10);
+        for _ in 0..10 {
+            vm.run(1).unwrap();
+        }
+        assert_eq!(vm.dt, 9);
+    }
+
+    #[test]
+    fn display_wait_throttles_draws_to_one_per_frame() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        };
+        let mut vm = VM::with_quirks(quirks);
+        // LD I, 0x050 ; DRW V0, V0, 5 ; DRW V0, V0, 5 ; JP 0x206
+        vm.load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+
+        // first frame: the first DRW waits for the vertical blank
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x202);
+        assert!(vm.is_stalled());
+        // second frame: the first DRW runs, the second waits although the frame had time left
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        assert!(vm.frame_buffer()[0][0]);
+        // third frame: the second DRW erases the glyph again
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x206);
+        assert!(!vm.frame_buffer()[0][0]);
+
+        // without the quirk both draws happen in the same frame
+        let mut vm = VM::new_with_seed(42);
+        vm.load_rom(&[0xA0, 0x50, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x06])
+            .unwrap();
+        vm.step_frame(3).unwrap();
+        assert_eq!(vm.pc(), 0x206);
+    }
+
+    #[test]
+    fn run_reports_stalled_ticks_separately() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        };
+        let mut vm = VM::with_quirks(quirks);
+        vm.load_rom(&[0xD0, 0x01, 0x12, 0x02]).unwrap();
+        let report = vm.run(3).unwrap();
+        assert_eq!((report.executed, report.stalled), (0, 3));
+        assert_eq!(report.stopped, Stopped::Budget);
+        assert_eq!(vm.pc(), 0x200);
+
+        // the timers step on the tenth tick, the DRW runs on the eleventh, then the jump halts
+        let report = vm.run(100).unwrap();
+        assert_eq!((report.executed, report.stalled), (2, 7));
+        assert_eq!(report.stopped, Stopped::Halted);
+    }
+
+    // CALL 0x208 ; LD V1, 1 ; JP 0x204 ; then at 0x208: LD V0, 7 ; CALL 0x20E ; RET ; at 0x20E: ADD V0, 1 ; RET
+    const NESTED_CALLS: [u8; 18] = [
+        0x22, 0x08, 0x61, 0x01, 0x12, 0x04, 0x00, 0x00, 0x60, 0x07, 0x22, 0x0E, 0x00, 0xEE, 0x70,
+        0x01, 0x00, 0xEE,
+    ];
+
+    #[test]
+    fn step_over_runs_a_call_to_completion() {
+        let mut vm = VM::new_with_seed(42);
+        vm.load_rom(&NESTED_CALLS).unwrap();
+        assert_eq!(vm.step_over(100).unwrap(), Stopped::Stepped);
+        assert_eq!(vm.pc(), 0x202);
+        assert_eq!(vm.sp(), 0);
+        assert_eq!(vm.register(0), 8);
+        // not a call: a single step
+        assert_eq!(vm.step_over(100).unwrap(), Stopped::Stepped);
+        assert_eq!(vm.pc(), 0x204);
+        assert_eq!(vm.register(1), 1);
+        // the budget runs out inside the subroutine
+        vm.load_rom(&NESTED_CALLS).unwrap();
+        assert_eq!(vm.step_over(2).unwrap(), Stopped::Budget);
+        assert_eq!(vm.pc(), 0x20A);
+    }
+
+    #[test]
+    fn step_out_resumes_at_the_caller() {
+        let mut vm = VM::new_with_seed(42);
+        vm.load_rom(&NESTED_CALLS).unwrap();
+        assert!(vm.step_out(100).is_err());
+        // into the outer subroutine, then into the inner one
+        vm.run(3).unwrap();
+        assert_eq!((vm.pc(), vm.sp()), (0x20E, 2));
+        assert_eq!(vm.step_out(100).unwrap(), Stopped::Stepped);
+        assert_eq!((vm.pc(), vm.sp()), (0x20C, 1));
+        assert_eq!(vm.register(0), 8);
+        assert_eq!(vm.step_out(100).unwrap(), Stopped::Stepped);
+        assert_eq!((vm.pc(), vm.sp()), (0x202, 0));
+    }
+
+    #[test]
+    fn assemble_into_patches_memory() {
+        let mut vm = VM::new_with_seed(42);
+        // LD V0, 1 ; LD V1, 2 ; LD V2, 3
+        vm.load_rom(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03]).unwrap();
+        vm.tick().unwrap();
+        assert_eq!(vm.assemble_into(0x202, "JP 0x204"), Result::Ok(2));
+        assert_eq!(vm.memory_slice(0x202, 2).unwrap(), &[0x12, 0x04]);
+        vm.tick().unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        assert_eq!(vm.register(1), 0);
+
+        assert_eq!(
+            vm.assemble_into(0x300, "CLS\n\nLD VA, 0x23\n"),
+            Result::Ok(4)
+        );
+        assert_eq!(
+            vm.memory_slice(0x300, 4).unwrap(),
+            &[0x00, 0xE0, 0x6A, 0x23]
+        );
+        assert_eq!(
+            vm.assemble_into(0xFFE, "CLS\nCLS"),
+            Err(AssembleError::DoesNotFit(4, 0xFFE))
+        );
+        assert!(vm.assemble_into(0x310, "CLS\nFOO V1").is_err());
+        assert_eq!(vm.memory_slice(0x310, 2).unwrap(), &[0x00, 0x00]);
+        assert_eq!(vm.memory_slice(0xFFE, 2).unwrap(), &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn same_register_shifts_agree_under_both_quirks() {
+        for shift_uses_vy in [false, true] {
+            let mut vm = VM::with_quirks(Quirks {
+                shift_uses_vy,
+                ..Quirks::default()
+            });
+            vm.registers[3] = 0x81;
+            vm.execute(OpCode::ShlRegister(V(3), V(3))).unwrap();
+            assert_eq!((vm.register(3), vm.register(0xF)), (0x02, 1));
+            vm.registers[3] = 0x81;
+            vm.execute(OpCode::ShrRegister(V(3), V(3))).unwrap();
+            assert_eq!((vm.register(3), vm.register(0xF)), (0x40, 1));
+        }
+    }
+
+    #[test]
+    fn host_timer_setters_count_down() {
+        let mut vm = VM::new_with_seed(42);
+        vm.set_delay_timer_value(2);
+        vm.set_sound_timer_value(1);
+        assert!(vm.sound_active());
+        vm.tick_timers();
+        assert_eq!((vm.dt, vm.st), (1, 0));
+        assert!(!vm.sound_active());
+        vm.tick_timers();
+        vm.tick_timers();
+        assert_eq!((vm.dt, vm.st), (0, 0));
+    }
+
+    #[test]
+    fn current_opcode_string_formats_pc_and_mnemonic() {
+        let mut vm = VM::new_with_seed(42);
+        vm.load_rom(&[0xD0, 0x15, 0x51, 0x21]).unwrap();
+        assert_eq!(vm.current_opcode_string(), "0x0200: DRW V0, V1, 5");
+        vm.pc = 0x202;
+        assert_eq!(
+            vm.current_opcode_string(),
+            "0x0202: cannot decode (0x5121 has its low nibble set where it must be zero)"
+        );
+    }
+
+    #[test]
+    fn fx55_fx65_leave_registers_above_x_alone() {
+        let mut vm = VM::new_with_seed(42);
+        vm.i = 0x300;
+        vm.memory[0x300..0x306].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+        vm.registers[5] = 0xEE;
+        vm.execute(OpCode::LoadRegisters(V(3))).unwrap();
+        assert_eq!(&vm.registers()[..6], &[1, 2, 3, 4, 0, 0xEE]);
+
+        vm.i = 0x400;
+        vm.execute(OpCode::SaveRegisters(V(3))).unwrap();
+        assert_eq!(vm.memory_slice(0x400, 6).unwrap(), &[1, 2, 3, 4, 0, 0]);
+    }
+
+    #[test]
+    fn memory_index_quirk_moves_i_for_save_and_load() {
+        for (memory_index, expected_i) in [
+            (MemoryIndex::Unchanged, 0x300),
+            (MemoryIndex::IncrementByX, 0x303),
+            (MemoryIndex::IncrementPastX, 0x304),
+        ] {
+            let quirks = Quirks {
+                memory_index,
+                ..Quirks::default()
+            };
+            for op in [OpCode::SaveRegisters(V(3)), OpCode::LoadRegisters(V(3))] {
+                let mut vm = VM::with_quirks(quirks);
+                vm.i = 0x300;
+                let effects = vm.execute_recorded(op).unwrap();
+                assert_eq!(vm.i(), expected_i);
+                assert_eq!(effects.i_written, memory_index != MemoryIndex::Unchanged);
+            }
+        }
+    }
+
+    #[test]
+    fn render_ascii_follows_the_resolution() {
+        let mut vm = VM::new_with_seed(42);
+        assert_eq!(vm.screen_dimensions(), (64, 32));
+        assert_eq!(vm.render_ascii().lines().count(), 32);
+        vm.set_hires(true);
+        assert_eq!(vm.screen_dimensions(), (128, 64));
+        let out = vm.render_ascii();
+        assert_eq!(out.lines().count(), 64);
+        assert!(out.lines().all(|line| line.chars().count() == 128));
+    }
+
+    #[test]
+    fn font_base_can_move_to_zero() {
+        let mut vm = VM::new_with_seed(42);
+        vm.set_font_base(0x000).unwrap();
+        vm.registers[1] = 0xA;
+        vm.execute(OpCode::LoadSprite(V(1))).unwrap();
+        assert_eq!(vm.i(), 0x032);
+        assert_eq!(
+            vm.memory_slice(0x032, 5).unwrap(),
+            &[0xF0, 0x90, 0xF0, 0x90, 0x90]
+        );
+        vm.registers[1] = 2;
+        vm.execute(OpCode::LoadLargeSprite(V(1))).unwrap();
+        assert_eq!(vm.i(), 0x050 + 20);
+        // what the new location does not cover of the old one is zeroed
+        assert!(vm
+            .memory_slice(0x0B4, 0x50)
+            .unwrap()
+            .iter()
+            .all(|&b| b == 0));
+        vm.cold_reset();
+        assert_eq!(
+            vm.memory_slice(0x000, 5).unwrap(),
+            &[0xF0, 0x90, 0x90, 0x90, 0xF0]
+        );
+        assert!(vm.set_font_base(0x1F0).is_err());
+    }
+
+    #[test]
+    fn add_register_records_reads_and_writes() {
+        let mut vm = VM::new_with_seed(0);
+        vm.registers[1] = 0xF0;
+        vm.registers[2] = 0x20;
+        let effects = vm
+            .execute_recorded(OpCode::AddRegister(V(1), V(2)))
+            .unwrap();
+        assert_eq!(
+            effects,
+            SideEffects {
+                registers_read: vec![1, 2],
+                registers_written: vec![1, 0x0F],
+                pc_delta: 0,
+                ..SideEffects::default()
+            }
+        );
+        assert_eq!(vm.registers[1], 0x10);
+        assert_eq!(vm.registers[0xF], 1);
+    }
+
+    #[test]
+    fn save_registers_records_the_memory_it_writes() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        let effects = vm.execute_recorded(OpCode::SaveRegisters(V(2))).unwrap();
+        assert_eq!(effects.registers_read, vec![0, 1, 2]);
+        assert_eq!(effects.memory_written, vec![0x300, 0x301, 0x302]);
+        assert!(effects.memory_read.is_empty());
+        assert!(!effects.i_written);
+    }
+
+    #[test]
+    fn load_key_records_a_write_only_once_a_key_arrives() {
+        let mut vm = VM::new_with_seed(0);
+        vm.pc = 0x202;
+        let waiting = vm.execute_recorded(OpCode::LoadKey(V(3))).unwrap();
+        assert_eq!(waiting.registers_written, Vec::<u8>::new());
+        assert_eq!(waiting.pc_delta, -2);
+
+        vm.pc = 0x202;
+        vm.set_key(0xA, true);
+        let pressed = vm.execute_recorded(OpCode::LoadKey(V(3))).unwrap();
+        assert_eq!(pressed.registers_written, vec![3]);
+        assert_eq!(pressed.pc_delta, 0);
+        assert_eq!(vm.registers[3], 0xA);
+    }
+
+    #[test]
+    fn with_rom_is_ready_to_step() {
+        // LD V1, 0x2A ; JP 0x200
+        let mut vm = VM::with_rom(&[0x61, 0x2A, 0x12, 0x00]).unwrap();
+        assert_eq!(vm.pc, 0x200);
+        assert_eq!(
+            vm.memory[FONT_BASE..FONT_BASE + 5],
+            [0xF0, 0x90, 0x90, 0x90, 0xF0]
+        );
+        vm.tick().unwrap();
+        assert_eq!(vm.registers[1], 0x2A);
+        assert_eq!(vm.pc, 0x202);
+        assert!(VM::with_rom(&[0; MEMORY_BYTES]).is_err());
+    }
+
+    #[test]
+    fn dxy0_draws_nothing_in_the_classic_mode() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        vm.memory[0x300..0x320].fill(0xFF);
+        vm.registers[0xF] = 1;
+        vm.execute(OpCode::Draw(V(0), V(1), 0)).unwrap();
+        assert!(vm.display.iter().flatten().all(|&lit| !lit));
+        assert_eq!(vm.registers[0xF], 0);
+    }
+
+    #[test]
+    fn dxy0_draws_a_16x16_sprite_in_high_resolution() {
+        let mut vm = VM::new_with_seed(0);
+        vm.set_hires(true);
+        vm.i = 0x300;
+        // each row is the left half lit then the right half lit, alternating
+        for row in 0..16 {
+            let word: u16 = if row % 2 == 0 { 0xFF00 } else { 0x00FF };
+            vm.memory[0x300 + row * 2..0x302 + row * 2].copy_from_slice(&word.to_be_bytes());
+        }
+        vm.registers[0] = 4;
+        vm.registers[1] = 2;
+        vm.execute(OpCode::Draw(V(0), V(1), 0)).unwrap();
+        for row in 0..16 {
+            let lit: Vec<usize> = (0..128).filter(|&x| vm.display[2 + row][x]).collect();
+            let first = if row % 2 == 0 { 4 } else { 12 };
+            assert_eq!(lit, (first..first + 8).collect::<Vec<_>>(), "row {}", row);
+        }
+        assert!(vm.display[1].iter().chain(&vm.display[18]).all(|&lit| !lit));
+        assert_eq!(vm.registers[0xF], 0);
+
+        vm.execute(OpCode::Draw(V(0), V(1), 0)).unwrap();
+        assert!(vm.display.iter().flatten().all(|&lit| !lit));
+        assert_eq!(vm.registers[0xF], 1);
+    }
+
+    #[test]
+    fn key_wait_and_display_wait_both_resolve() {
+        let quirks = Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        };
+        // LD I, 0x050 ; DRW V0, V0, 5 ; LD V1, K ; DRW V0, V0, 5 ; JP 0x208
+        let rom = [0xA0, 0x50, 0xD0, 0x05, 0xF1, 0x0A, 0xD0, 0x05, 0x12, 0x08];
+
+        let mut vm = VM::with_quirks(quirks);
+        vm.load_rom(&rom).unwrap();
+        // the first DRW waits for the vertical blank, then Fx0A waits for a key across frames
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x202);
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        assert!(vm.frame_buffer()[0][0]);
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        // the key arrives: Fx0A completes and the second DRW uses the blank that passed while waiting
+        vm.set_key(0x7, true);
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.registers[1], 0x7);
+        assert_eq!(vm.pc(), 0x208);
+        assert!(!vm.frame_buffer()[0][0]);
+
+        // a key pressed while the first DRW is stalled is still there for Fx0A
+        let mut vm = VM::with_quirks(quirks);
+        vm.load_rom(&rom).unwrap();
+        vm.set_key(0x3, true);
+        vm.step_frame(10).unwrap();
+        assert!(vm.is_stalled());
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.registers[1], 0x3);
+        // the blank was spent on the first DRW, so the second one waits for the next frame
+        assert_eq!(vm.pc(), 0x206);
+        assert!(vm.is_stalled());
+        vm.step_frame(10).unwrap();
+        assert_eq!(vm.pc(), 0x208);
+    }
+
+    #[test]
+    fn reading_a_never_written_register_warns_at_its_address() {
+        // LD V1, 0x01 ; ADD V1, V5 ; ADD V1, V5
+        let mut vm = VM::with_rom(&[0x61, 0x01, 0x81, 0x54, 0x81, 0x54]).unwrap();
+        vm.track_uninitialized_reads(true);
+        vm.tick().unwrap();
+        assert!(vm.take_warnings().is_empty());
+        vm.tick().unwrap();
+        assert_eq!(
+            vm.take_warnings(),
+            vec!["0x0202: V5 is read before being written".to_owned()]
+        );
+        // the warning repeats for every other instruction reading V5 until it is written
+        vm.tick().unwrap();
+        assert_eq!(
+            vm.take_warnings(),
+            vec!["0x0204: V5 is read before being written".to_owned()]
+        );
+
+        // without the mode nothing is collected
+        let mut vm = VM::with_rom(&[0x81, 0x54]).unwrap();
+        vm.tick().unwrap();
+        assert!(vm.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn memory_wrap_quirk_wraps_i_past_4k() {
+        let mut vm = VM::new_with_seed(0);
+        assert!(vm.quirks.memory_wrap);
+        vm.memory[0xFFE] = 0x11;
+        vm.memory[0xFFF] = 0x22;
+        vm.memory[0x000] = 0x33;
+        vm.memory[0x001] = 0x44;
+        vm.i = 0x0FFE;
+        vm.execute(OpCode::LoadRegisters(V(3))).unwrap();
+        assert_eq!(vm.registers[0..4], [0x11, 0x22, 0x33, 0x44]);
+
+        vm.registers[0] = 0x99;
+        vm.execute(OpCode::SaveRegisters(V(2))).unwrap();
+        assert_eq!(vm.memory[0xFFE..], [0x99, 0x22]);
+        assert_eq!(vm.memory[0x000], 0x33);
+
+        let mut vm = VM::with_quirks(Quirks {
+            memory_wrap: false,
+            ..Quirks::default()
+        });
+        vm.i = 0x0FFE;
+        let err = vm.execute(OpCode::LoadRegisters(V(3))).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::MemoryAccess(0x1000))
+        );
+    }
+
+    #[test]
+    fn draw_sets_vf_only_when_it_erases() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        vm.memory[0x300..0x302].copy_from_slice(&[0b1100_0000, 0b0100_0000]);
+        vm.registers[0] = 10;
+        vm.registers[1] = 5;
+        vm.registers[0xF] = 1;
+        vm.execute(OpCode::Draw(V(0), V(1), 2)).unwrap();
+        assert_eq!(vm.registers[0xF], 0);
+        assert!(vm.display[5][10] && vm.display[5][11]);
+        assert!(!vm.display[6][10] && vm.display[6][11]);
+        assert_eq!(vm.display.iter().flatten().filter(|&&lit| lit).count(), 3);
+
+        vm.execute(OpCode::Draw(V(0), V(1), 2)).unwrap();
+        assert_eq!(vm.registers[0xF], 1);
+        assert!(vm.display.iter().flatten().all(|&lit| !lit));
+    }
+
+    #[test]
+    fn draw_wraps_past_the_right_and_bottom_edges() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        vm.memory[0x300..0x302].copy_from_slice(&[0b1111_0000, 0b1000_0001]);
+        vm.registers[0] = 62;
+        vm.registers[1] = 31;
+        vm.execute(OpCode::Draw(V(0), V(1), 2)).unwrap();
+        assert_eq!(vm.registers[0xF], 0);
+        let lit: Vec<(usize, usize)> = (0..DISPLAY_HEIGHT)
+            .flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| vm.display[y][x])
+            .collect();
+        // the first row wraps from x=62 to the left edge, the second row wraps from y=31 to the top
+        assert_eq!(
+            lit,
+            vec![(5, 0), (62, 0), (0, 31), (1, 31), (62, 31), (63, 31)]
+        );
+    }
+
+    #[test]
+    fn solid_rows_match_two_half_rows_aligned_or_not() {
+        // a background with a few lit pixels so collisions show up
+        let background = |vm: &mut VM| {
+            for x in [3, 9, 14, 58, 63] {
+                vm.display[4][x] = true;
+            }
+        };
+        for x in [0, 8, 16, 56, 3, 21, 60] {
+            // a whole 0xFF row, on a byte boundary or not
+            let mut solid = VM::new_with_seed(0);
+            background(&mut solid);
+            solid.i = 0x300;
+            solid.memory[0x300] = 0xFF;
+            solid.registers[0] = x;
+            solid.registers[1] = 4;
+            solid.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+
+            // the same 8 pixels as two half rows of 0xF0
+            let mut halves = VM::new_with_seed(0);
+            background(&mut halves);
+            halves.i = 0x300;
+            halves.memory[0x300] = 0xF0;
+            halves.registers[0] = x;
+            halves.registers[1] = 4;
+            halves.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+            let first_collision = halves.registers[0xF];
+            halves.registers[0] = x + 4;
+            halves.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+
+            assert_eq!(solid.display, halves.display, "x = {}", x);
+            assert_eq!(
+                solid.registers[0xF],
+                first_collision | halves.registers[0xF],
+                "x = {}",
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn load_rom_copies_the_program_to_0x200() {
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom(&[0x12, 0x34, 0x56]).unwrap();
+        assert_eq!(vm.pc(), 0x200);
+        assert_eq!(vm.memory[0x200..0x204], [0x12, 0x34, 0x56, 0x00]);
+
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom(&[]).unwrap();
+        assert_eq!(vm.pc(), 0x200);
+        assert!(vm.memory[PROGRAM_START..].iter().all(|&b| b == 0));
+
+        // the largest ROM fills program memory exactly, one byte more does not fit
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom(&[0xAA; MEMORY_BYTES - PROGRAM_START]).unwrap();
+        assert_eq!(vm.memory[MEMORY_BYTES - 1], 0xAA);
+        let err = vm
+            .load_rom(&[0xAA; MEMORY_BYTES - PROGRAM_START + 1])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::RomTooLarge(MEMORY_BYTES - PROGRAM_START + 1))
+        );
+
+        let path = std::env::temp_dir().join(format!("rs-chip-8-{}.ch8", std::process::id()));
+        std::fs::write(&path, [0x00, 0xE0]).unwrap();
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vm.memory[0x200..0x202], [0x00, 0xE0]);
+        assert!(vm.load_rom_file(&path).is_err());
+    }
+
+    #[test]
+    fn add_and_sub_wrap_around() {
+        let mut vm = VM::new_with_seed(0);
+        vm.execute(OpCode::Add(V(0), Byte(0xFF))).unwrap();
+        vm.execute(OpCode::Add(V(0), Byte(0xFF))).unwrap();
+        assert_eq!(vm.registers[0], 0xFE);
+
+        vm.registers[1] = 0x10;
+        vm.registers[2] = 0x30;
+        vm.execute(OpCode::SubRegister(V(1), V(2))).unwrap();
+        assert_eq!(vm.registers[1], 0xE0);
+        assert_eq!(vm.registers[0xF], 0);
+
+        vm.registers[3] = 0x30;
+        vm.registers[4] = 0x10;
+        vm.execute(OpCode::SubNotBorrowRegister(V(3), V(4)))
+            .unwrap();
+        assert_eq!(vm.registers[3], 0xE0);
+        assert_eq!(vm.registers[0xF], 0);
+
+        vm.registers[5] = 0xF0;
+        vm.registers[6] = 0x20;
+        vm.execute(OpCode::AddRegister(V(5), V(6))).unwrap();
+        assert_eq!(vm.registers[5], 0x10);
+        assert_eq!(vm.registers[0xF], 1);
+    }
+
+    #[test]
+    fn shl_carries_out_the_high_bit() {
+        let mut vm = VM::new_with_seed(0);
+        vm.registers[1] = 0x81;
+        vm.execute(OpCode::ShlRegister(V(1), V(1))).unwrap();
+        assert_eq!(vm.registers[1], 0x02);
+        assert_eq!(vm.registers[0xF], 1);
+
+        vm.execute(OpCode::ShlRegister(V(1), V(1))).unwrap();
+        assert_eq!(vm.registers[1], 0x04);
+        assert_eq!(vm.registers[0xF], 0);
+    }
+
+    #[test]
+    fn call_and_ret_guard_the_stack() {
+        // CALL 0x200, calling itself forever
+        let mut vm = VM::with_rom(&[0x22, 0x00]).unwrap();
+        for depth in 1..=STACK_LENGTH {
+            vm.tick().unwrap();
+            assert_eq!(vm.sp() as usize, depth);
+        }
+        assert_eq!(vm.stack, [0x202; STACK_LENGTH]);
+        let err = vm.tick().unwrap_err();
+        assert_eq!(err.downcast_ref::<VmError>(), Some(&VmError::StackOverflow));
+        assert_eq!(vm.sp() as usize, STACK_LENGTH);
+
+        // RET
+        let mut vm = VM::with_rom(&[0x00, 0xEE]).unwrap();
+        let err = vm.tick().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::StackUnderflow)
+        );
+        assert_eq!(vm.sp(), 0);
+
+        // CALL 0x204 ; JP 0x202 ; RET
+        let mut vm = VM::with_rom(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]).unwrap();
+        vm.tick().unwrap();
+        assert_eq!((vm.pc(), vm.sp(), vm.stack[0]), (0x204, 1, 0x202));
+        vm.tick().unwrap();
+        assert_eq!((vm.pc(), vm.sp()), (0x202, 0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn restore_returns_to_the_snapshot_point() {
+        let state = |vm: &VM| {
+            (
+                vm.memory,
+                vm.registers,
+                (vm.i, vm.dt, vm.st, vm.pc, vm.sp),
+                vm.stack,
+                vm.display.clone(),
+            )
+        };
+        // LD I, 0x050 ; CALL 0x206 ; ADD V0, 0x05 ; DRW V0, V1, 5 ; LD DT, V0 ; JP 0x206
+        let mut vm = VM::with_rom(&[
+            0xA0, 0x50, 0x22, 0x06, 0x00, 0x00, 0x70, 0x05, 0xD0, 0x15, 0xF0, 0x15, 0x12, 0x06,
+        ])
+        .unwrap();
+        vm.set_key(0x4, true);
+        vm.run(6).unwrap();
+        let saved = vm.snapshot();
+        let at_snapshot = state(&vm);
+
+        vm.run(20).unwrap();
+        vm.set_key(0x4, false);
+        assert_ne!(state(&vm), at_snapshot);
+
+        let restored = VM::restore(&saved).unwrap();
+        assert_eq!(state(&restored), at_snapshot);
+        assert!(restored.pheriphal.keypad.is_down(0x4));
+        assert!(VM::restore(&saved[..saved.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn warm_reset_keeps_ram_and_cold_reset_reloads_it() {
+        let mut vm = VM::with_rom(&[0x60, 0x07]).unwrap();
+        vm.tick().unwrap();
+        vm.memory[0x800] = 0x5A;
+        vm.memory[0x200] = 0xFF;
+
+        vm.warm_reset();
+        assert_eq!(vm.memory[0x800], 0x5A);
+        assert_eq!(vm.memory[0x200], 0xFF);
+        assert_eq!(vm.registers[0], 0);
+        assert_eq!(vm.pc(), 0x200);
+
+        vm.cold_reset();
+        assert_eq!(vm.memory[0x800], 0x00);
+        // the ROM and the font are loaded again
+        assert_eq!(vm.memory[0x200..0x202], [0x60, 0x07]);
+        assert_eq!(vm.memory[FONT_BASE], 0xF0);
+        assert_eq!(vm.pc(), 0x200);
+    }
+
+    #[test]
+    fn sprite_rows_expands_the_font_digit_8() {
+        let vm = VM::new_with_seed(0);
+        let rows = vm.sprite_rows((FONT_BASE + 8 * FONT_SPRITE_BYTES) as u16, 5);
+        let pattern: Vec<String> = rows
+            .iter()
+            .map(|row| row.iter().map(|&on| if on { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(
+            pattern,
+            vec!["####....", "#..#....", "####....", "#..#....", "####...."]
+        );
+        // rows past the end of memory are left out
+        assert_eq!(vm.sprite_rows(0xFFE, 5).len(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn memory_checksum_tracks_ram_through_a_save_state() {
+        let mut vm = VM::with_rom(&[0x00, 0xE0]).unwrap();
+        let before = vm.memory_checksum();
+        vm.memory[0x900] = 0x01;
+        let poked = vm.memory_checksum();
+        assert_ne!(poked, before);
+
+        let restored = VM::restore(&vm.snapshot()).unwrap();
+        assert_eq!(restored.memory_checksum(), poked);
+    }
+
+    #[test]
+    fn nop_encodes_to_zero_and_changes_nothing() {
+        assert_eq!(u16::from(OpCode::nop()), 0x0000);
+        let mut vm = VM::with_rom(&[0x00, 0x00]).unwrap();
+        vm.registers[3] = 0x33;
+        let before = (vm.memory, vm.registers, vm.i, vm.sp, vm.display.clone());
+        vm.tick().unwrap();
+        assert_eq!(
+            (vm.memory, vm.registers, vm.i, vm.sp, vm.display.clone()),
+            before
+        );
+        assert_eq!(vm.pc(), 0x202);
+    }
+
+    #[test]
+    fn replace_rng_keeps_cpu_state() {
+        // LD V1, 0x11 ; RND V0, 0xFF ; RND V0, 0xFF
+        let mut vm = VM::with_rom(&[0x61, 0x11, 0xC0, 0xFF, 0xC0, 0xFF]).unwrap();
+        vm.tick().unwrap();
+        vm.replace_rng(Box::new(crate::interp::rng::FixedSequence(vec![
+            0x42, 0x17,
+        ])));
+        assert_eq!((vm.pc(), vm.registers[1]), (0x202, 0x11));
+        vm.tick().unwrap();
+        assert_eq!(vm.registers[0], 0x42);
+        vm.tick().unwrap();
+        assert_eq!(vm.registers[0], 0x17);
+    }
+
+    #[test]
+    fn dump_memory_hex_formats_like_hexdump() {
+        let mut vm = VM::new_with_seed(0);
+        vm.memory[0x300..0x315].copy_from_slice(b"CHIP-8\x00\x01\xFFhello, world");
+        assert_eq!(
+            vm.dump_memory_hex(0x300, 18),
+            "0300  43 48 49 50 2D 38 00 01  FF 68 65 6C 6C 6F 2C 20  |CHIP-8...hello, |\n\
+             0310  77 6F                                             |wo|\n"
+        );
+        // clipped to the end of memory
+        assert_eq!(vm.dump_memory_hex(0xFFF, 16).lines().count(), 1);
+        assert_eq!(vm.dump_memory_hex(0x1000, 16), "");
+    }
+
+    #[test]
+    fn an_undecodable_word_is_a_clean_error() {
+        // every opcode is implemented now, what is left to fail is a word that is not an instruction
+        let mut vm = VM::with_rom(&[0x51, 0x21]).unwrap();
+        let err = vm.tick().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DecodeError>(),
+            Some(&DecodeError::ReservedBitsSet(0x5121))
+        );
+        assert_eq!(vm.pc(), 0x200);
+    }
+
+    #[test]
+    fn font_digit_a_is_loaded_where_fx29_points() {
+        let mut vm = VM::new_with_seed(0);
+        assert_eq!(vm.memory[0x082..0x087], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+        vm.registers[4] = 0xA;
+        vm.execute(OpCode::LoadSprite(V(4))).unwrap();
+        assert_eq!(vm.i(), 0x082);
+    }
+
+    #[test]
+    fn little_endian_roms_load_the_same_program() {
+        let mut big = VM::new_with_seed(0);
+        big.load_rom_endian(&[0x6A, 0x23, 0xD1, 0x25, 0x07], Endian::Big)
+            .unwrap();
+        let mut little = VM::new_with_seed(0);
+        little
+            .load_rom_endian(&[0x23, 0x6A, 0x25, 0xD1, 0x07], Endian::Little)
+            .unwrap();
+        assert_eq!(big.memory, little.memory);
+        assert_eq!(
+            little.instruction_at(0x200),
+            Result::Ok(OpCode::Load(V(0xA), Byte(0x23)))
+        );
+    }
+
+    #[test]
+    fn bcd_stores_hundreds_tens_and_ones() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        for (value, digits) in [(0, [0, 0, 0]), (255, [2, 5, 5]), (137, [1, 3, 7])] {
+            vm.registers[2] = value;
+            vm.execute(OpCode::LoadBCD(V(2))).unwrap();
+            assert_eq!(vm.memory[0x300..0x303], digits, "{}", value);
+        }
+        assert_eq!(vm.i(), 0x300);
+    }
+
+    #[test]
+    fn tick_fetches_decodes_and_executes_in_order() {
+        // LD V0, 0x05 ; LD V1, 0x07 ; ADD V0, V1 ; SE V0, 0x0C ; LD V2, 0xFF ; LD V3, 0x01
+        let mut vm = VM::with_rom(&[
+            0x60, 0x05, 0x61, 0x07, 0x80, 0x14, 0x30, 0x0C, 0x62, 0xFF, 0x63, 0x01,
+        ])
+        .unwrap();
+        for _ in 0..5 {
+            vm.tick().unwrap();
+        }
+        assert_eq!(vm.registers[..4], [0x0C, 0x07, 0x00, 0x01]);
+        assert_eq!(vm.registers[0xF], 0);
+        assert_eq!(vm.pc(), 0x20C);
+    }
+
+    #[test]
+    fn skp_and_sknp_follow_the_keypad() {
+        let mut vm = VM::new_with_seed(0);
+        vm.registers[1] = 0xB;
+        vm.pc = 0x202;
+        vm.set_key(0xB, true);
+        vm.execute(OpCode::SkipKey(V(1))).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        vm.execute(OpCode::SkipNotKey(V(1))).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+
+        vm.set_key(0xB, false);
+        vm.execute(OpCode::SkipKey(V(1))).unwrap();
+        assert_eq!(vm.pc(), 0x204);
+        vm.execute(OpCode::SkipNotKey(V(1))).unwrap();
+        assert_eq!(vm.pc(), 0x206);
+    }
+
+    #[test]
+    fn timers_count_down_and_stop_at_zero() {
+        let mut vm = VM::new_with_seed(0);
+        vm.set_delay_timer_value(3);
+        vm.set_sound_timer_value(2);
+        assert!(vm.sound_active());
+        vm.tick_timers();
+        assert_eq!((vm.dt, vm.st), (2, 1));
+        for _ in 0..5 {
+            vm.tick_timers();
+        }
+        assert_eq!((vm.dt, vm.st), (0, 0));
+        assert!(!vm.sound_active());
+    }
+
+    #[test]
+    fn shift_quirk_picks_the_source_register() {
+        for (shift_uses_vy, shr, shl) in [(false, 0x08, 0x20), (true, 0x40, 0x02)] {
+            let mut vm = VM::with_quirks(Quirks {
+                shift_uses_vy,
+                ..Quirks::default()
+            });
+            // Vx = 0x10, Vy = 0x81
+            vm.registers[1] = 0x10;
+            vm.registers[2] = 0x81;
+            vm.execute(OpCode::ShrRegister(V(1), V(2))).unwrap();
+            assert_eq!(
+                (vm.register(1), vm.register(0xF)),
+                (shr, shift_uses_vy as u8)
+            );
+            vm.registers[1] = 0x10;
+            vm.execute(OpCode::ShlRegister(V(1), V(2))).unwrap();
+            assert_eq!(
+                (vm.register(1), vm.register(0xF)),
+                (shl, shift_uses_vy as u8)
+            );
+            assert_eq!(vm.register(2), 0x81);
+        }
+    }
+
+    #[test]
+    fn fx55_leaves_i_where_the_quirk_says() {
+        for (memory_index, expected_i) in [
+            (MemoryIndex::Unchanged, 0x400),
+            (MemoryIndex::IncrementByX, 0x405),
+            (MemoryIndex::IncrementPastX, 0x406),
+        ] {
+            // LD I, 0x400 ; LD [I], V5
+            let mut vm = VmBuilder::new()
+                .quirks(Quirks {
+                    memory_index,
+                    ..Quirks::default()
+                })
+                .build();
+            vm.load_rom(&[0xA4, 0x00, 0xF5, 0x55]).unwrap();
+            vm.tick().unwrap();
+            vm.tick().unwrap();
+            assert_eq!(vm.i(), expected_i, "{:?}", memory_index);
+        }
+    }
+
+    #[test]
+    fn the_seed_decides_the_random_sequence() {
+        let rolls = |seed: u64| -> Vec<u8> {
+            let mut vm = VM::new_with_seed(seed);
+            (0..16)
+                .map(|_| {
+                    vm.execute(OpCode::Random(V(0), Byte(0xFF))).unwrap();
+                    vm.register(0)
+                })
+                .collect()
+        };
+        assert_eq!(rolls(7), rolls(7));
+        assert_ne!(rolls(7), rolls(8));
+    }
+
+    #[test]
+    fn memory_access_past_4k_is_a_clean_error() {
+        let mut vm = VM::with_quirks(Quirks {
+            memory_wrap: false,
+            ..Quirks::default()
+        });
+        vm.i = 4095;
+        let err = vm.execute(OpCode::SaveRegisters(V(5))).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::MemoryAccess(0x1000))
+        );
+        assert!(vm.memory_slice(0xFFF, 2).is_err());
+    }
+
+    #[test]
+    fn a_jump_to_itself_halts_after_one_tick() {
+        let mut vm = VM::with_rom(&[0x12, 0x00]).unwrap();
+        assert!(!vm.is_halted());
+        vm.tick().unwrap();
+        assert!(vm.is_halted());
+        assert_eq!(vm.pc(), 0x200);
+    }
+
+    #[test]
+    fn accessors_read_back_what_opcodes_set() {
+        // LD V3, 0x33 ; LD I, 0x300 ; LD [I], V3
+        let mut vm = VM::with_rom(&[0x63, 0x33, 0xA3, 0x00, 0xF3, 0x55]).unwrap();
+        for _ in 0..3 {
+            vm.tick().unwrap();
+        }
+        assert_eq!(vm.register(3), 0x33);
+        assert_eq!(vm.registers()[3], 0x33);
+        assert_eq!(vm.i(), 0x300);
+        assert_eq!(vm.pc(), 0x206);
+        assert_eq!(vm.sp(), 0);
+        assert_eq!(vm.memory_slice(0x303, 1).unwrap(), &[0x33]);
+    }
+
+    #[test]
+    fn run_stops_at_a_breakpoint_and_continues_past_it() {
+        // LD V0, 0x01 ; LD V1, 0x02 ; LD V2, 0x03 ; JP 0x206
+        let mut vm = VM::with_rom(&[0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x06]).unwrap();
+        vm.add_breakpoint(0x204);
+        let report = vm.run(100).unwrap();
+        assert_eq!(report.stopped, Stopped::Breakpoint(0x204));
+        assert_eq!(report.executed, 2);
+        assert_eq!(vm.register(2), 0);
+
+        let report = vm.run(100).unwrap();
+        assert_eq!(report.stopped, Stopped::Halted);
+        assert_eq!(vm.register(2), 3);
+
+        vm.remove_breakpoint(0x204);
+        vm.warm_reset();
+        assert_eq!(vm.run(100).unwrap().stopped, Stopped::Halted);
+    }
+
+    #[test]
+    fn eti_660_programs_load_at_0x600() {
+        let mut vm = VM::new_with_seed(0);
+        vm.load_rom_at(&[0x6A, 0x23], 0x600).unwrap();
+        assert_eq!(vm.pc(), 0x600);
+        assert_eq!(vm.memory[0x600], 0x6A);
+        assert_eq!(vm.memory[0x200], 0x00);
+        vm.tick().unwrap();
+        assert_eq!(vm.register(0xA), 0x23);
+
+        let err = vm.load_rom_at(&[0x00], 0x100).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::InvalidProgramStart(0x100))
+        );
+    }
+
+    #[test]
+    fn render_ascii_shows_a_drawn_digit() {
+        let mut vm = VM::new_with_seed(0);
+        // the 0 glyph at (2, 1)
+        vm.i = FONT_BASE as u16;
+        vm.registers[0] = 2;
+        vm.registers[1] = 1;
+        vm.execute(OpCode::Draw(V(0), V(1), 5)).unwrap();
+        let out = vm.render_ascii();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 32);
+        assert!(lines[0].chars().all(|c| c == ' '));
+        assert!(lines[1].starts_with("  ████ "));
+        assert!(lines[2].starts_with("  █  █ "));
+        assert!(lines[5].starts_with("  ████ "));
+    }
+
+    #[test]
+    fn callbacks_fire_on_visible_changes_only() {
+        use std::cell::{Cell, RefCell};
+        use std::rc::Rc;
+
+        let draws = Rc::new(Cell::new(0));
+        let sounds = Rc::new(RefCell::new(Vec::new()));
+        // CLS ; LD I, 0x050 ; DRW V0, V0, 5 ; CLS ; LD V1, 0x02 ; LD ST, V1 ; JP 0x20C
+        let mut vm = VM::with_rom(&[
+            0x00, 0xE0, 0xA0, 0x50, 0xD0, 0x05, 0x00, 0xE0, 0x61, 0x02, 0xF1, 0x18, 0x12, 0x0C,
+        ])
+        .unwrap();
+        let counter = Rc::clone(&draws);
+        vm.set_draw_callback(Box::new(move |_| counter.set(counter.get() + 1)));
+        let log = Rc::clone(&sounds);
+        vm.set_sound_callback(Box::new(move |on| log.borrow_mut().push(on)));
+
+        for _ in 0..7 {
+            vm.tick().unwrap();
+        }
+        // the first CLS had nothing to clear
+        assert_eq!(draws.get(), 2);
+        assert_eq!(*sounds.borrow(), vec![true]);
+        vm.tick_timers();
+        vm.tick_timers();
+        vm.tick_timers();
+        assert_eq!(*sounds.borrow(), vec![true, false]);
+    }
+
+    #[test]
+    fn high_resolution_coordinates_do_not_wrap() {
+        for (hires, expected) in [(false, (6, 8)), (true, (70, 40))] {
+            let mut vm = VmBuilder::new().hires(hires).build();
+            vm.i = 0x300;
+            vm.memory[0x300] = 0x80;
+            vm.registers[0] = 70;
+            vm.registers[1] = 40;
+            vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+            let (width, height) = vm.screen_dimensions();
+            let lit: Vec<(usize, usize)> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| vm.frame_buffer()[y][x])
+                .collect();
+            assert_eq!(lit, vec![expected], "hires {}", hires);
+        }
+    }
+
+    #[test]
+    fn superchip_scrolls_move_pixels() {
+        let lit = |vm: &VM| -> Vec<(usize, usize)> {
+            let (width, height) = vm.screen_dimensions();
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| vm.frame_buffer()[y][x])
+                .collect()
+        };
+        let mut vm = VM::new_with_seed(0);
+        vm.display[2][1] = true;
+        vm.display[30][62] = true;
+
+        vm.execute(OpCode::ScrollDown(3)).unwrap();
+        // the pixel pushed past the bottom is gone, not wrapped
+        assert_eq!(lit(&vm), vec![(1, 5)]);
+        vm.execute(OpCode::ScrollRight).unwrap();
+        assert_eq!(lit(&vm), vec![(5, 5)]);
+        vm.execute(OpCode::ScrollLeft).unwrap();
+        vm.execute(OpCode::ScrollLeft).unwrap();
+        assert!(lit(&vm).is_empty());
+    }
+
+    #[test]
+    fn high_opcode_makes_room_for_x_100() {
+        // HIGH ; LD I, 0x050 ; LD V0, 100 ; DRW V0, V1, 1 ; LOW
+        let mut vm =
+            VM::with_rom(&[0x00, 0xFF, 0xA0, 0x50, 0x60, 0x64, 0xD0, 0x11, 0x00, 0xFE]).unwrap();
+        for _ in 0..4 {
+            vm.tick().unwrap();
+        }
+        assert_eq!(vm.screen_dimensions(), (128, 64));
+        assert!((100..104).all(|x| vm.frame_buffer()[0][x]));
+        assert!(!vm.frame_buffer()[0][100 % 64]);
+
+        vm.tick().unwrap();
+        assert_eq!(vm.screen_dimensions(), (64, 32));
+        assert_eq!(vm.frame_buffer().len(), 32);
+    }
+
+    #[test]
+    fn fx30_points_i_at_the_large_digit() {
+        let mut vm = VM::new_with_seed(0);
+        vm.registers[3] = 7;
+        vm.execute(OpCode::LoadLargeSprite(V(3))).unwrap();
+        // the large font follows the 16 small digits of 5 bytes, 10 bytes per large digit
+        assert_eq!(vm.i(), 0x050 + 16 * 5 + 7 * 10);
+        assert_eq!(
+            vm.memory[vm.i() as usize..vm.i() as usize + 10],
+            LARGE_FONT[7].0
+        );
+    }
+
+    #[test]
+    fn step_frame_steps_the_timers_once() {
+        // JP 0x200
+        let mut vm = VM::with_rom(&[0x12, 0x00]).unwrap();
+        vm.set_delay_timer_value(10);
+        vm.set_sound_timer_value(10);
+        for frame in 1..=3 {
+            vm.step_frame(crate::interp::clock::Clock::default().instructions_for_next_frame())
+                .unwrap();
+            assert_eq!((vm.dt, vm.st), (10 - frame, 10 - frame));
+        }
+        vm.step_frame(0).unwrap();
+        assert_eq!(vm.dt, 6);
+    }
+
+    #[test]
+    fn jump_v0_wraps_within_12_bits() {
+        let mut vm = VM::new_with_seed(0);
+        vm.registers[0] = 0xFF;
+        vm.execute(OpCode::JumpV0(Addr(0xFFF))).unwrap();
+        assert_eq!(vm.pc(), (0xFF + 0xFFF) & 0x0FFF);
+        assert_eq!(vm.pc(), 0x0FE);
+    }
+
+    #[test]
+    fn add_i_flag_quirk_near_the_boundary() {
+        for add_i_sets_vf in [false, true] {
+            let mut vm = VM::with_quirks(Quirks {
+                add_i_sets_vf,
+                ..Quirks::default()
+            });
+            vm.registers[0xF] = 0x77;
+            vm.registers[1] = 0x01;
+            vm.i = 0x0FFE;
+            vm.execute(OpCode::AddI(V(1))).unwrap();
+            assert_eq!(vm.i(), 0x0FFF);
+            let below = vm.register(0xF);
+            vm.execute(OpCode::AddI(V(1))).unwrap();
+            assert_eq!(vm.i(), 0x1000);
+            let past = vm.register(0xF);
+            if add_i_sets_vf {
+                assert_eq!((below, past), (0, 1));
+            } else {
+                assert_eq!((below, past), (0x77, 0x77));
+            }
+        }
+    }
+
+    #[test]
+    fn a_plugged_in_screen_sees_every_pixel_change() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        #[derive(Clone, Default)]
+        struct RecordingScreen {
+            calls: Rc<RefCell<Vec<String>>>,
+        }
+
+        impl Screen for RecordingScreen {
+            fn clear(&mut self) {
+                self.calls.borrow_mut().push("clear".to_owned());
+            }
+
+            fn draw_pixel(&mut self, x: usize, y: usize, on: bool) -> bool {
+                self.calls.borrow_mut().push(format!("{},{},{}", x, y, on));
+                false
+            }
+        }
+
+        let screen = RecordingScreen::default();
+        let calls = Rc::clone(&screen.calls);
+        let mut vm = VM::new_with_seed(0);
+        vm.set_screen(Box::new(screen));
+        // set_screen brings the new screen up to date, starting with a clear
+        assert_eq!(*calls.borrow(), vec!["clear"]);
+
+        calls.borrow_mut().clear();
+        vm.i = 0x300;
+        vm.memory[0x300] = 0b1010_0000;
+        vm.registers[0] = 3;
+        vm.registers[1] = 4;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert_eq!(*calls.borrow(), vec!["3,4,true", "5,4,true", "clear"]);
+    }
+
+    #[test]
+    fn a_scripted_keypad_feeds_fx0a() {
+        #[derive(Clone)]
+        struct ScriptedKeypad {
+            // None is a tick with no key pressed
+            presses: Vec<Option<u8>>,
+        }
+
+        impl Keypad for ScriptedKeypad {
+            fn is_down(&self, _key: u8) -> bool {
+                false
+            }
+
+            fn wait_key(&mut self) -> Option<u8> {
+                if self.presses.is_empty() {
+                    None
+                } else {
+                    self.presses.remove(0)
+                }
+            }
+        }
+
+        // LD V0, K ; LD V1, K ; JP 0x204
+        let mut vm = VM::with_rom(&[0xF0, 0x0A, 0xF1, 0x0A, 0x12, 0x04]).unwrap();
+        vm.set_keypad(Box::new(ScriptedKeypad {
+            presses: vec![None, None, Some(0x3), None, Some(0xC)],
+        }));
+        let report = vm.run(10).unwrap();
+        assert_eq!(report.stopped, Stopped::Halted);
+        assert_eq!(report.executed, 6);
+        assert_eq!(vm.registers[..2], [0x3, 0xC]);
+    }
+
+    #[test]
+    fn the_buzzer_follows_the_sound_timer() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        #[derive(Clone, Default)]
+        struct RecordingBuzzer {
+            calls: Rc<RefCell<Vec<bool>>>,
+        }
+
+        impl Buzzer for RecordingBuzzer {
+            fn set_playing(&mut self, playing: bool) {
+                self.calls.borrow_mut().push(playing);
+            }
+        }
+
+        let buzzer = RecordingBuzzer::default();
+        let calls = Rc::clone(&buzzer.calls);
+        // LD V0, 0x02 ; LD ST, V0
+        let mut vm = VmBuilder::new().buzzer(Box::new(buzzer)).build();
+        vm.load_rom(&[0x60, 0x02, 0xF0, 0x18]).unwrap();
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+        assert_eq!(*calls.borrow(), vec![true]);
+        vm.tick_timers();
+        assert_eq!(*calls.borrow(), vec![true]);
+        vm.tick_timers();
+        assert_eq!(*calls.borrow(), vec![true, false]);
+    }
+
+    #[test]
+    fn trace_records_the_state_before_each_instruction() {
+        // LD V0, 0x05 ; ADD V0, 0x03 ; JP 0x204
+        let mut vm = VM::with_rom(&[0x60, 0x05, 0x70, 0x03, 0x12, 0x04]).unwrap();
+        vm.tick().unwrap();
+        assert!(vm.trace().is_empty());
+        vm.enable_trace(true);
+        vm.tick().unwrap();
+        vm.tick().unwrap();
+
+        let mut registers = [0; REGISTER_COUNT];
+        registers[0] = 0x05;
+        assert_eq!(
+            vm.trace(),
+            &[
+                TraceEntry {
+                    pc: 0x202,
+                    opcode_word: 0x7003,
+                    mnemonic: "ADD V0, 0x03".to_owned(),
+                    registers_before: registers,
+                },
+                TraceEntry {
+                    pc: 0x204,
+                    opcode_word: 0x1204,
+                    mnemonic: "JP 0x204".to_owned(),
+                    registers_before: [0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                },
+            ]
+        );
+
+        // only the most recent entries are kept
+        vm.set_trace_capacity(3);
+        for _ in 0..10 {
+            vm.tick().unwrap();
+        }
+        assert_eq!(vm.trace().len(), 3);
+        assert!(vm.trace().iter().all(|entry| entry.pc == 0x204));
+    }
+
+    #[test]
+    fn clipping_counts_collided_and_bottom_clipped_rows() {
+        let mut vm = VM::with_quirks(Quirks {
+            clip_sprites_with_counting: true,
+            ..Quirks::default()
+        });
+        vm.set_hires(true);
+        vm.i = 0x300;
+        vm.memory[0x300..0x320].fill(0xFF);
+        // a 16x16 sprite at y = 60 has 4 rows on screen and 12 below the bottom edge
+        vm.registers[0] = 120;
+        vm.registers[1] = 60;
+        vm.execute(OpCode::Draw(V(0), V(1), 0)).unwrap();
+        assert_eq!(vm.registers[0xF], 12);
+        let lit: Vec<(usize, usize)> = (0..HIRES_DISPLAY_HEIGHT)
+            .flat_map(|y| (0..HIRES_DISPLAY_WIDTH).map(move |x| (x, y)))
+            .filter(|&(x, y)| vm.display[y][x])
+            .collect();
+        // neither the rows below the bottom nor the columns past the right edge wrap around
+        let expected: Vec<(usize, usize)> = (60..64)
+            .flat_map(|y| (120..128).map(move |x| (x, y)))
+            .collect();
+        assert_eq!(lit, expected);
+
+        // drawn again, the 4 visible rows collide and the 12 clipped ones still count
+        vm.execute(OpCode::Draw(V(0), V(1), 0)).unwrap();
+        assert_eq!(vm.registers[0xF], 16);
+        assert!(vm.display.iter().flatten().all(|&lit| !lit));
+    }
+
+    #[test]
+    fn persistence_fades_a_pixel_after_it_goes_dark() {
+        let mut vm = VM::new_with_seed(0);
+        vm.set_persistence(true);
+        vm.i = 0x300;
+        vm.memory[0x300] = 0x80;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        vm.tick_timers();
+        assert_eq!(vm.intensity(0, 0), 255);
+
+        // erasing the pixel leaves it dark for collisions but still glowing
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        assert!(!vm.frame_buffer()[0][0]);
+        assert_eq!(vm.intensity(0, 0), 255);
+        let mut fading = Vec::new();
+        for _ in 0..9 {
+            vm.tick_timers();
+            fading.push(vm.intensity(0, 0));
+        }
+        assert_eq!(fading, vec![127, 63, 31, 15, 7, 3, 1, 0, 0]);
+
+        // without persistence brightness is the display itself
+        vm.set_persistence(false);
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        assert_eq!(vm.intensity(0, 0), 0);
+    }
+
+    #[test]
+    fn noop_sled_detector_fires_after_the_threshold() {
+        let mut vm = VM::new_with_seed(0);
+        // JP 0x400, into RAM nothing was loaded to
+        vm.load_rom(&[0x14, 0x00]).unwrap();
+        let mut unguarded = vm.clone();
+        vm.detect_noop_sled(Some(8));
+        let report = vm.run(1000).unwrap();
+        assert_eq!(report.stopped, Stopped::RanOffTheRails(0x400));
+        assert_eq!(report.executed, 1 + 8);
+        assert_eq!(vm.pc(), 0x410);
+
+        // off by default, the zeroes are just executed until the budget runs out
+        let report = unguarded.run(1000).unwrap();
+        assert_eq!(report.stopped, Stopped::Budget);
+        assert_eq!(report.executed, 1000);
+    }
+
+    #[test]
+    fn draw_origin_wraps_at_the_configured_modulus() {
+        let mut vm = VM::with_quirks(Quirks {
+            draw_origin_wrap: Some((32, 16)),
+            ..Quirks::default()
+        });
+        vm.i = 0x300;
+        vm.memory[0x300] = 0x80;
+        vm.registers[0] = 40;
+        vm.registers[1] = 20;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        // (40, 20) wraps to (8, 4) rather than staying where the 64x32 screen would put it
+        assert!(vm.display[4][8]);
+        assert_eq!(vm.display.iter().flatten().filter(|&&lit| lit).count(), 1);
+
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        vm.memory[0x300] = 0x80;
+        vm.registers[0] = 40;
+        vm.registers[1] = 20;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        assert!(vm.display[20][40]);
+    }
+
+    #[test]
+    fn strict_draw_source_rejects_sprites_below_0x200() {
+        let mut vm = VM::with_quirks(Quirks {
+            strict_draw_source: true,
+            ..Quirks::default()
+        });
+        vm.i = 0x100;
+        let err = vm.execute(OpCode::Draw(V(0), V(1), 5)).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::DrawFromReserved(0x100))
+        );
+        assert!(vm.display.iter().flatten().all(|&lit| !lit));
+
+        // the font digits are what programs are meant to draw from there
+        vm.execute(OpCode::LoadSprite(V(0))).unwrap();
+        vm.execute(OpCode::Draw(V(0), V(1), 5)).unwrap();
+        assert!(vm.display[0][0]);
+        vm.execute(OpCode::LoadLargeSprite(V(0))).unwrap();
+        vm.execute(OpCode::Draw(V(0), V(1), 10)).unwrap();
+
+        vm.quirks.strict_draw_source = false;
+        vm.i = 0x100;
+        vm.execute(OpCode::Draw(V(0), V(1), 5)).unwrap();
+    }
+
+    #[test]
+    fn the_first_cls_marks_dirty_and_repeats_do_not() {
+        let mut vm = VM::new_with_seed(0);
+        assert!(!vm.take_dirty());
+        // the display is already blank, the first CLS still asks for one present
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert!(vm.take_dirty());
+        assert!(!vm.take_dirty());
+        vm.execute(OpCode::ClearScreen).unwrap();
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert!(!vm.take_dirty());
+
+        vm.i = 0x300;
+        vm.memory[0x300] = 0x80;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        assert!(vm.take_dirty());
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert!(vm.take_dirty());
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert!(!vm.take_dirty());
+    }
+
+    #[test]
+    fn protected_interpreter_region_rejects_writes() {
+        let mut vm = VM::with_quirks(Quirks {
+            protect_interpreter_region: true,
+            ..Quirks::default()
+        });
+        vm.i = 0x100;
+        vm.registers[0] = 0xAB;
+        let before = vm.memory[0x100];
+        let err = vm.execute(OpCode::SaveRegisters(V(0))).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::WriteToReserved(0x100))
+        );
+        assert_eq!(vm.memory[0x100], before);
+        let err = vm.poke(0x1FF, 1).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VmError>(),
+            Some(&VmError::WriteToReserved(0x1FF))
+        );
+        vm.poke(0x200, 1).unwrap();
+        assert_eq!(vm.memory[0x200], 1);
+
+        vm.quirks.protect_interpreter_region = false;
+        vm.execute(OpCode::SaveRegisters(V(0))).unwrap();
+        assert_eq!(vm.memory[0x100], 0xAB);
+    }
+
+    // every flag-setting opcode writes VF after its result, so with VF as the destination the flag is what survives
+    mod vf_order {
+        use super::*;
+
+        // runs op with VF = vf and V1 = v1, returns VF afterwards
+        fn vf_after(op: OpCode, vf: u8, v1: u8) -> u8 {
+            let mut vm = VM::new_with_seed(0);
+            vm.registers[0xF] = vf;
+            vm.registers[1] = v1;
+            vm.execute(op).unwrap();
+            vm.registers[0xF]
+        }
+
+        #[test]
+        fn add_register_keeps_the_carry() {
+            assert_eq!(vf_after(OpCode::AddRegister(V(0xF), V(1)), 0xFF, 1), 1);
+            assert_eq!(vf_after(OpCode::AddRegister(V(0xF), V(1)), 0x10, 1), 0);
+        }
+
+        #[test]
+        fn sub_register_keeps_not_borrow() {
+            assert_eq!(vf_after(OpCode::SubRegister(V(0xF), V(1)), 5, 3), 1);
+            assert_eq!(vf_after(OpCode::SubRegister(V(0xF), V(1)), 3, 5), 0);
+        }
+
+        #[test]
+        fn sub_not_borrow_register_keeps_not_borrow() {
+            assert_eq!(
+                vf_after(OpCode::SubNotBorrowRegister(V(0xF), V(1)), 3, 5),
+                1
+            );
+            assert_eq!(
+                vf_after(OpCode::SubNotBorrowRegister(V(0xF), V(1)), 5, 3),
+                0
+            );
+        }
+
+        #[test]
+        fn shr_register_keeps_the_low_bit() {
+            assert_eq!(vf_after(OpCode::ShrRegister(V(0xF), V(0xF)), 0b11, 0), 1);
+            assert_eq!(vf_after(OpCode::ShrRegister(V(0xF), V(0xF)), 0b10, 0), 0);
+        }
+
+        #[test]
+        fn shl_register_keeps_the_high_bit() {
+            assert_eq!(vf_after(OpCode::ShlRegister(V(0xF), V(0xF)), 0x80, 0), 1);
+            assert_eq!(vf_after(OpCode::ShlRegister(V(0xF), V(0xF)), 0x40, 0), 0);
+        }
+
+        #[test]
+        fn draw_keeps_the_collision() {
+            // VF holds both coordinates, the sprite is the font digit 0 at (2, 2)
+            let mut vm = VM::new_with_seed(0);
+            vm.i = FONT_BASE as u16;
+            vm.registers[0xF] = 2;
+            vm.execute(OpCode::Draw(V(0xF), V(0xF), 5)).unwrap();
+            assert_eq!(vm.registers[0xF], 0);
+            assert!(vm.display[2][2]);
+            vm.registers[0xF] = 2;
+            vm.execute(OpCode::Draw(V(0xF), V(0xF), 5)).unwrap();
+            assert_eq!(vm.registers[0xF], 1);
+        }
+    }
+
+    #[test]
+    fn trace_filter_keeps_only_the_chosen_categories() {
+        let mut vm = VM::new_with_seed(0);
+        // LD V0, 1 ; LD I, 0x050 ; DRW V0, V0, 5 ; ADD V0, 1 ; JP 0x208
+        vm.load_rom(&[0x60, 0x01, 0xA0, 0x50, 0xD0, 0x05, 0x70, 0x01, 0x12, 0x08])
+            .unwrap();
+        vm.enable_trace(true);
+        vm.set_trace_filter(OpCategory::FlowControl.mask() | OpCategory::Display.mask());
+        vm.run(100).unwrap();
+        let traced: Vec<(u16, &str)> = vm
+            .trace()
+            .iter()
+            .map(|entry| (entry.pc, entry.mnemonic.as_str()))
+            .collect();
+        assert_eq!(traced, vec![(0x204, "DRW V0, V0, 5"), (0x208, "JP 0x208")]);
+    }
+
+    #[test]
+    fn fx0a_catches_a_key_pressed_and_released_in_one_batch() {
+        let mut vm = VM::new_with_seed(0);
+        // LD V0, K ; SKP V0
+        vm.load_rom(&[0xF0, 0x0A, 0xE0, 0x9E]).unwrap();
+        vm.tick().unwrap();
+        assert_eq!(vm.pc(), 0x200);
+
+        vm.apply_key_events(&[(7, true), (7, false)]);
+        vm.tick().unwrap();
+        assert_eq!(vm.register(0), 7);
+        assert_eq!(vm.pc(), 0x202);
+        // the skip only sees the state after the batch, the key is up again
+        vm.tick().unwrap();
+        assert_eq!(vm.pc(), 0x204);
+    }
+
+    #[test]
+    fn set_pixels_yields_exactly_the_drawn_sprite() {
+        let mut vm = VM::new_with_seed(0);
+        vm.i = 0x300;
+        vm.memory[0x300..0x302].copy_from_slice(&[0b1010_0000, 0b0100_0000]);
+        vm.registers[0] = 10;
+        vm.registers[1] = 5;
+        vm.execute(OpCode::Draw(V(0), V(1), 2)).unwrap();
+        let lit: Vec<(usize, usize)> = vm.set_pixels().collect();
+        assert_eq!(lit, vec![(10, 5), (12, 5), (11, 6)]);
+        assert!(vm.is_pixel_set_at_index(5 * DISPLAY_WIDTH + 10));
+        assert!(!vm.is_pixel_set_at_index(5 * DISPLAY_WIDTH + 11));
+        assert!(vm.is_pixel_set_at_index(6 * DISPLAY_WIDTH + 11));
+        assert!(!vm.is_pixel_set_at_index(DISPLAY_WIDTH * DISPLAY_HEIGHT));
+    }
+
+    #[test]
+    fn sound_callback_fires_on_the_tick_fx18_runs() {
+        let mut vm = VM::new_with_seed(0);
+        // LD V0, 1 ; LD ST, V0 ; JP 0x204
+        vm.load_rom(&[0x60, 0x01, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        let mut events = Vec::new();
+        vm.run_frame_with_callbacks(1, |on| events.push(on))
+            .unwrap();
+        assert!(events.is_empty());
+
+        // st goes 0 -> 1 on the Fx18 tick and back to 0 on the timer step of the same frame,
+        // checking only at the frame boundary would see no change at all
+        vm.run_frame_with_callbacks(2, |on| events.push(on))
+            .unwrap();
+        assert_eq!(events, vec![true, false]);
+    }
+
+    #[test]
+    fn quirk_usage_records_shifts_with_a_second_register() {
+        let mut vm = VM::new_with_seed(0);
+        // LD V1, 6 ; SHR V0, V0 ; JP 0x204
+        vm.load_rom(&[0x61, 0x06, 0x80, 0x06, 0x12, 0x04]).unwrap();
+        vm.run(10).unwrap();
+        // shifting a register in place is the same under both settings
+        assert_eq!(vm.quirk_usage(), QuirkUsageReport::default());
+
+        // LD V1, 6 ; SHL V0, V1 ; SHR V0, V1 ; JP 0x206
+        vm.load_rom(&[0x61, 0x06, 0x80, 0x1E, 0x80, 0x16, 0x12, 0x06])
+            .unwrap();
+        vm.run(10).unwrap();
+        assert_eq!(
+            vm.quirk_usage(),
+            QuirkUsageReport {
+                shift_source: true,
+                ..QuirkUsageReport::default()
+            }
+        );
+    }
+
+    #[test]
+    fn a_loop_reading_an_unwritten_register_warns_once() {
+        // LD V1, 0x01 ; ADD V1, V5 ; JP 0x202
+        let mut vm = VM::with_rom(&[0x61, 0x01, 0x81, 0x54, 0x12, 0x02]).unwrap();
+        vm.track_uninitialized_reads(true);
+        vm.run(1000).unwrap();
+        assert_eq!(
+            vm.take_warnings(),
+            vec!["0x0202: V5 is read before being written".to_owned()]
+        );
+        vm.run(1000).unwrap();
+        assert!(vm.take_warnings().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn save_state_round_trips_through_a_file() {
+        let rom = [0xA0, 0x50, 0x60, 0x07, 0xD0, 0x05, 0xF0, 0x18, 0x12, 0x08];
+        let path = std::env::temp_dir().join(format!("rs-chip-8-{}.state", std::process::id()));
+        let mut vm = VM::with_rom(&rom).unwrap();
+        vm.set_key(0x3, true);
+        vm.run(5).unwrap();
+        vm.save_state(&path).unwrap();
+
+        let mut loaded = VM::with_rom(&rom).unwrap();
+        loaded.load_state(&path).unwrap();
+        assert_eq!(loaded.snapshot(), vm.snapshot());
+        assert!(loaded.sound_active());
+
+        // the same file against another program is refused and leaves the VM alone
+        let mut other = VM::with_rom(&[0x12, 0x00]).unwrap();
+        let err = other.load_state(&path).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VmError>(),
+            Some(VmError::SaveStateRomMismatch(..))
+        ));
+        assert_eq!(other.pc(), 0x200);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(other.load_state(&path).is_err());
+    }
+
+    #[test]
+    fn collision_map_lists_the_overlap() {
+        let mut vm = VM::new_with_seed(0);
+        vm.record_collisions(true);
+        vm.i = 0x300;
+        // a 2x2 block drawn at (4, 4) and again at (5, 5) overlaps in (5, 5) only
+        vm.memory[0x300..0x302].fill(0b1100_0000);
+        vm.registers[0] = 4;
+        vm.execute(OpCode::Draw(V(0), V(0), 2)).unwrap();
+        assert!(vm.last_collision().is_empty());
+        vm.registers[0] = 5;
+        vm.execute(OpCode::Draw(V(0), V(0), 2)).unwrap();
+        assert_eq!(vm.last_collision(), &[(5, 5)]);
+        assert_eq!(vm.registers[0xF], 1);
+
+        // a solid row across the bottom row of what is left, (5, 6) and (6, 6)
+        vm.memory[0x302] = 0xFF;
+        vm.i = 0x302;
+        vm.registers[0] = 0;
+        vm.registers[1] = 6;
+        vm.execute(OpCode::Draw(V(0), V(1), 1)).unwrap();
+        assert_eq!(vm.last_collision(), &[(5, 6), (6, 6)]);
+    }
+
+    #[test]
+    fn tick_with_timers_steps_the_timers_at_60hz_of_the_clock() {
+        let mut vm = VM::new_with_seed(0);
+        // CLS ; JP 0x200
+        vm.load_rom(&[0x00, 0xE0, 0x12, 0x00]).unwrap();
+        vm.set_clock(Clock::new(700));
+        vm.set_delay_timer_value(200);
+        // half a second at 700 instructions per second is 30 steps, 700 isn't a multiple of 60
+        // so they come every 11 or 12 ticks
+        for _ in 0..350 {
+            vm.tick_with_timers().unwrap();
+        }
+        assert_eq!(vm.dt, 170);
+        for _ in 0..350 {
+            vm.tick_with_timers().unwrap();
+        }
+        assert_eq!(vm.dt, 140);
+    }
+
+    #[test]
+    fn hex_digit_rom_matches_the_font_frame() {
+        // draws the 16 font digits side by side along the top, 4 pixels apart, then halts
+        const HEX_DIGITS_ROM: [u8; 20] = [
+            0x60, 0x00, // LD V0, 0
+            0x61, 0x00, // LD V1, 0
+            0x62, 0x00, // LD V2, 0
+            0xF0, 0x29, // LD F, V0
+            0xD1, 0x25, // DRW V1, V2, 5
+            0x70, 0x01, // ADD V0, 1
+            0x71, 0x04, // ADD V1, 4
+            0x30, 0x10, // SE V0, 0x10
+            0x12, 0x06, // JP 0x206
+            0x12, 0x12, // JP 0x212
+        ];
+        let mut expected = VM::new_with_seed(0);
+        for (digit, sprite) in FONT.iter().enumerate() {
+            for (y, &byte) in sprite.0.iter().enumerate() {
+                for bit in 0..4 {
+                    expected.display[y][digit * 4 + bit] = byte & (0x80 >> bit) != 0;
+                }
+            }
+        }
+        assert_eq!(
+            VM::run_headless_to_hash(&HEX_DIGITS_ROM, 1000).unwrap(),
+            expected.framebuffer_hash()
+        );
+        assert_ne!(
+            VM::run_headless_to_hash(&HEX_DIGITS_ROM, 20).unwrap(),
+            expected.framebuffer_hash()
+        );
+    }
+
+    #[test]
+    fn a_dt_polling_loop_ends_after_the_clock_rate_says() {
+        // LD V0, 10 ; LD DT, V0 ; LD V1, DT ; SE V1, 0 ; JP 0x204 ; JP 0x20A
+        let rom = [
+            0x60, 0x0A, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x12, 0x0A,
+        ];
+        for rate in [600, 700, 1200] {
+            let mut vm = VM::with_rom(&rom).unwrap();
+            vm.set_clock(Clock::new(rate));
+            let report = vm.run(100_000).unwrap();
+            assert_eq!(report.stopped, Stopped::Halted);
+            // 10 timer steps take 10/60 of a second of instructions, then up to two passes
+            // through the loop see dt reach zero and get to the final jump
+            let expected = (10 * rate).div_ceil(60) as usize;
+            assert!(
+                (expected..=expected + 6).contains(&report.executed),
+                "{} instructions at {} per second",
+                report.executed,
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn cls_blanks_the_frame_buffer() {
+        let mut vm = VM::new_with_seed(0);
+        vm.execute(OpCode::LoadSprite(V(0))).unwrap();
+        vm.execute(OpCode::Draw(V(0), V(0), 5)).unwrap();
+        assert!(vm.frame_buffer().iter().flatten().any(|&lit| lit));
+        vm.execute(OpCode::ClearScreen).unwrap();
+        assert_eq!(vm.frame_buffer().len(), DISPLAY_HEIGHT);
+        assert!(vm
+            .frame_buffer()
+            .iter()
+            .all(|row| row.len() == DISPLAY_WIDTH && row.iter().all(|&lit| !lit)));
+    }
+
+    #[test]
+    fn diagnostics_note_unknown_opcodes_and_keep_running() {
+        // LD V0, 1 ; 0x5121 ; ADD V0, 1 ; 0xF0FF ; JP 0x208
+        let rom = [0x60, 0x01, 0x51, 0x21, 0x70, 0x01, 0xF0, 0xFF, 0x12, 0x08];
+        let mut vm = VM::with_rom(&rom).unwrap();
+        vm.collect_diagnostics(true);
+        let report = vm.run(100).unwrap();
+        assert_eq!(report.stopped, Stopped::Halted);
+        assert_eq!(vm.register(0), 2);
+        assert_eq!(
+            vm.drain_diagnostics(),
+            vec![
+                (0x202, DecodeError::ReservedBitsSet(0x5121)),
+                (0x206, DecodeError::UnknownFx(0xFF)),
+            ]
+        );
+        assert!(vm.drain_diagnostics().is_empty());
+
+        // without the mode the first one stops the run
+        let mut vm = VM::with_rom(&rom).unwrap();
+        assert!(vm.run(100).is_err());
+    }
+
+    #[test]
+    fn builder_with_only_a_seed_matches_new_with_seed() {
+        let state = |vm: &VM| {
+            (
+                vm.memory,
+                vm.registers,
+                (vm.i, vm.dt, vm.st, vm.pc, vm.sp),
+                vm.stack,
+                vm.display.clone(),
+                vm.quirks,
+                vm.screen_dimensions(),
+            )
+        };
+        let mut built = VmBuilder::new().seed(7).build();
+        let mut plain = VM::new_with_seed(7);
+        assert_eq!(state(&built), state(&plain));
+        // the same seed gives the same random sequence
+        for x in 0..4 {
+            built.execute(OpCode::Random(V(x), Byte(0xFF))).unwrap();
+            plain.execute(OpCode::Random(V(x), Byte(0xFF))).unwrap();
+        }
+        assert_eq!(built.registers(), plain.registers());
+    }
+
+    #[test]
+    fn builder_builds_a_cosmac_vip_with_a_fixed_seed() {
+        // the example from the VmBuilder comment
+        let vm = VmBuilder::new()
+            .seed(42)
+            .quirks(Quirks {
+                shift_uses_vy: true,
+                memory_index: MemoryIndex::IncrementPastX,
+                ..Quirks::default()
+            })
+            .build();
+        assert!(vm.quirks.shift_uses_vy);
+        assert_eq!(vm.quirks.memory_index, MemoryIndex::IncrementPastX);
+        assert_eq!(vm.screen_dimensions(), (DISPLAY_WIDTH, DISPLAY_HEIGHT));
+        let hires = VmBuilder::new().seed(42).hires(true).build();
+        assert_eq!(
+            hires.screen_dimensions(),
+            (HIRES_DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT)
+        );
+    }
 }